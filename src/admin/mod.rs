@@ -22,6 +22,7 @@ use crate::{
     },
     core::{constant_time_eq, ProxyError},
     plugins::build_plugin,
+    proxy::consumer::ProxyConsumer,
     utils::response::{CommonErrors, ResponseBuilder},
 };
 
@@ -164,6 +165,10 @@ impl AdminResource for config::Route {
     }
 }
 
+impl AdminResource for config::StreamRoute {
+    const RESOURCE_TYPE: &'static str = "stream_routes";
+}
+
 impl AdminResource for config::Upstream {
     const RESOURCE_TYPE: &'static str = "upstreams";
 }
@@ -184,6 +189,33 @@ impl AdminResource for config::GlobalRule {
     }
 }
 
+impl AdminResource for config::PluginConfig {
+    const RESOURCE_TYPE: &'static str = "plugin_configs";
+
+    fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
+        validate_plugins(&resource.plugins)
+    }
+}
+
+impl AdminResource for config::Consumer {
+    const RESOURCE_TYPE: &'static str = "consumers";
+
+    fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
+        validate_plugins(&resource.plugins)?;
+        ProxyConsumer::new(resource.clone())
+            .map(|_| ())
+            .map_err(ApiError::ProxyError)
+    }
+}
+
+impl AdminResource for config::ConsumerGroup {
+    const RESOURCE_TYPE: &'static str = "consumer_groups";
+
+    fn validate_plugins_if_supported(resource: &Self) -> ApiResult<()> {
+        validate_plugins(&resource.plugins)
+    }
+}
+
 impl AdminResource for config::SSL {
     const RESOURCE_TYPE: &'static str = "ssls";
 }
@@ -205,6 +237,7 @@ macro_rules! admin_handler {
 
 admin_handler!(ResourceHandler);
 admin_handler!(GetHandler);
+admin_handler!(PatchHandler);
 admin_handler!(DeleteHandler);
 admin_handler!(ListHandler);
 
@@ -283,6 +316,74 @@ impl<T: AdminResource> Handler for GetHandler<T> {
     }
 }
 
+// PATCH handler - applies a JSON merge patch (RFC 7396) to the stored resource,
+// e.g. `{"status": 0}` to disable a route without resending it.
+#[async_trait]
+impl<T: AdminResource> Handler for PatchHandler<T> {
+    async fn handle(
+        &self,
+        etcd: &EtcdClientWrapper,
+        http_session: &mut ServerSession,
+        params: RequestParams,
+    ) -> ApiResult<ApiResponse> {
+        http_session.validate_content_type()?;
+
+        let body_data = read_request_body(http_session)
+            .await
+            .map_err(|e| ApiError::RequestBodyReadError(e.to_string()))?;
+
+        let key = ResourceHandler::<T>::extract_key(&params)?;
+
+        let current = match etcd.get(&key).await {
+            Err(e) => return Err(ApiError::EtcdGetError(e.to_string())),
+            Ok(Some(value)) => value,
+            Ok(None) => return Err(ApiError::InvalidRequest("Resource not found".into())),
+        };
+
+        let parse = |data: &[u8]| {
+            serde_json::from_slice::<serde_json::Value>(data).map_err(|e| {
+                ApiError::ProxyError(ProxyError::serialization_error("Failed to parse JSON", e))
+            })
+        };
+        let mut resource = parse(&current)?;
+        merge_patch(&mut resource, parse(&body_data)?);
+
+        let merged = serde_json::to_vec(&resource).map_err(|e| {
+            ApiError::ProxyError(ProxyError::serialization_error(
+                "Failed to serialize resource",
+                e,
+            ))
+        })?;
+
+        T::validate_resource(&merged)?;
+
+        etcd.put(&key, merged).await?;
+
+        Ok(ResponseBuilder::success_http(Vec::new(), None))
+    }
+}
+
+/// Applies a JSON merge patch: objects merge recursively, `null` removes a field,
+/// any other value replaces the target.
+fn merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    match patch {
+        serde_json::Value::Object(patch) => {
+            if !target.is_object() {
+                *target = serde_json::Value::Object(serde_json::Map::new());
+            }
+            let target = target.as_object_mut().expect("target is an object");
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(&key);
+                } else {
+                    merge_patch(target.entry(key).or_insert(serde_json::Value::Null), value);
+                }
+            }
+        }
+        patch => *target = patch,
+    }
+}
+
 // DELETE handler
 #[async_trait]
 impl<T: AdminResource> Handler for DeleteHandler<T> {
@@ -363,9 +464,13 @@ impl AdminHttpApp {
 
         // Register routes with type safety and reduced boilerplate
         this.register_resource_routes::<config::Route>()
+            .register_resource_routes::<config::StreamRoute>()
             .register_resource_routes::<config::Upstream>()
             .register_resource_routes::<config::Service>()
             .register_resource_routes::<config::GlobalRule>()
+            .register_resource_routes::<config::PluginConfig>()
+            .register_resource_routes::<config::Consumer>()
+            .register_resource_routes::<config::ConsumerGroup>()
             .register_resource_routes::<config::SSL>();
 
         this
@@ -377,6 +482,7 @@ impl AdminHttpApp {
 
         self.route(&path, Method::PUT, Box::new(ResourceHandler::<T>::new()))
             .route(&path, Method::GET, Box::new(GetHandler::<T>::new()))
+            .route(&path, Method::PATCH, Box::new(PatchHandler::<T>::new()))
             .route(&path, Method::DELETE, Box::new(DeleteHandler::<T>::new()))
             .route(&list_path, Method::GET, Box::new(ListHandler::<T>::new()));
 
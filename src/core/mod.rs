@@ -14,6 +14,7 @@ pub mod status;
 // Re-export all public items so external modules can use `crate::core::*`
 pub use error::{ErrorContext, ProxyError, ProxyResult};
 pub use plugin::{
-    apply_regex_uri_template, constant_time_eq, sort_plugins_by_priority_desc, PluginCreateFn,
-    ProxyContext, ProxyPlugin, ProxyPluginExecutor, RouteContext, UpstreamSelector,
+    apply_regex_uri_template, constant_time_eq, sort_plugins_by_priority_desc, InflightRequest,
+    PluginCreateFn, PooledConnection, ProxyContext, ProxyPlugin, ProxyPluginExecutor, RouteContext,
+    UpstreamOutcome, UpstreamSelector,
 };
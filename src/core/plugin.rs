@@ -7,7 +7,7 @@ use std::{any::Any, collections::HashMap, sync::Arc, time::Instant};
 use async_trait::async_trait;
 use bytes::Bytes;
 use once_cell::sync::Lazy;
-use pingora_core::upstreams::peer::HttpPeer;
+use pingora_core::{protocols::l4::socket::SocketAddr, upstreams::peer::HttpPeer};
 use pingora_error::{Error, Result};
 use pingora_http::{RequestHeader, ResponseHeader};
 use pingora_proxy::Session;
@@ -27,8 +27,8 @@ use pingora_load_balancing::Backend;
 /// Decouples route logic from specific upstream implementations, enabling
 /// different load balancing strategies and upstream configurations.
 pub trait UpstreamSelector: Send + Sync {
-    /// Select a backend for the given session
-    fn select_backend(&self, session: &mut Session) -> Option<Backend>;
+    /// Select a backend for the given session, avoiding `excluded` nodes unless no other is healthy
+    fn select_backend(&self, session: &mut Session, excluded: &[SocketAddr]) -> Option<Backend>;
 
     /// Get the number of retries configured for this upstream
     fn get_retries(&self) -> Option<usize>;
@@ -36,11 +36,49 @@ pub trait UpstreamSelector: Send + Sync {
     /// Get the retry timeout configured for this upstream
     fn get_retry_timeout(&self) -> Option<u64>;
 
+    /// Get the failures besides connect errors that are retried for this upstream
+    fn get_retry_on(&self) -> Option<&config::RetryOn>;
+
     /// Get the pass host configuration for this upstream
     fn get_pass_host(&self) -> &config::UpstreamPassHost;
 
     /// Rewrite the upstream host in the request header if needed
     fn upstream_host_rewrite(&self, upstream_request: &mut RequestHeader);
+
+    /// Report the outcome of a request proxied to `peer` for passive health checks
+    fn report_outcome(&self, peer: &HttpPeer, outcome: UpstreamOutcome);
+
+    /// Start tracking a request to `peer`, if the load balancer needs per-node load
+    fn start_request(&self, peer: &HttpPeer) -> Option<Box<dyn InflightRequest>>;
+
+    /// Count the upstream `connection` against the keepalive pool, if the upstream bounds it
+    fn pool_connection(&self, connection: u64) -> Option<Box<dyn PooledConnection>>;
+}
+
+/// A request in flight to an upstream node, released when dropped
+pub trait InflightRequest: Send + Sync {
+    /// Record that the node answered, feeding its response time to the load balancer
+    fn complete(&mut self);
+}
+
+/// An upstream connection counted against its upstream's keepalive pool until dropped
+pub trait PooledConnection: Send + Sync {
+    /// Whether the pool has room to keep the connection once the request completes
+    fn keepalive(&self) -> bool;
+
+    /// Record that the request completed and the connection went back to the pool idle
+    fn release(&mut self);
+}
+
+/// Result of talking to an upstream node, as seen by passive health checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamOutcome {
+    /// The node answered with this HTTP status
+    Status(u16),
+    /// Connecting to, reading from or writing to the node failed
+    TcpFailure,
+    /// Connecting to, reading from or writing to the node timed out
+    Timeout,
 }
 
 /// Trait for route behavior that can be used in proxy context
@@ -51,14 +89,24 @@ pub trait RouteContext: Send + Sync {
     /// Get the service ID if available
     fn service_id(&self) -> Option<&str>;
 
-    /// Select an HTTP peer for the route
-    fn select_http_peer(&self, session: &mut Session) -> ProxyResult<Box<HttpPeer>>;
+    /// Select a backend for the route, with route timeouts applied to its HttpPeer
+    fn select_backend(
+        &self,
+        session: &mut Session,
+        excluded: &[SocketAddr],
+    ) -> ProxyResult<Backend>;
 
     /// Build plugin executor for this route
     fn build_plugin_executor(&self) -> Arc<ProxyPluginExecutor>;
 
     /// Resolve upstream for this route
     fn resolve_upstream(&self) -> Option<Arc<dyn UpstreamSelector>>;
+
+    /// Timeouts for upgraded connections, or `None` to keep the route timeouts
+    fn websocket_timeout(&self) -> Option<config::WebSocketTimeout>;
+
+    /// Whether upgrade requests are proxied as plain requests
+    fn websocket_disabled(&self) -> bool;
 }
 
 // =============================================================================
@@ -80,8 +128,16 @@ pub struct ProxyContext {
     pub upstream_override: Option<Arc<dyn UpstreamSelector>>,
     // Selected HTTP peer for the upstream request.
     pub peer: Option<Box<HttpPeer>>,
+    /// Load tracking of the request to `peer`, for load-aware balancing.
+    pub inflight: Option<Box<dyn InflightRequest>>,
+    /// Keepalive pool slot of the connection to `peer`, when the upstream bounds its pool.
+    pub pooled_connection: Option<Box<dyn PooledConnection>>,
+    /// Metadata of the node `peer` belongs to, if it has any.
+    pub node_metadata: Option<Arc<config::NodeMetadata>>,
     /// Number of retry attempts so far.
     pub tries: usize,
+    /// Nodes that failed an earlier attempt, avoided when retrying.
+    pub failed_peers: Vec<SocketAddr>,
     /// Executor for route-specific plugins.
     pub plugin: Arc<ProxyPluginExecutor>,
     /// Executor for global plugins.
@@ -90,6 +146,10 @@ pub struct ProxyContext {
     pub request_start: Instant,
     /// Unique request identifier, set by request-id plugin if enabled.
     pub request_id: Option<String>,
+    /// Consumer identified by an auth plugin, if any.
+    pub consumer: Option<Arc<config::Consumer>>,
+    /// Plugins of the consumer's group, pending merge into the route executor.
+    pub consumer_plugins: Option<Arc<ProxyPluginExecutor>>,
     /// Custom variables available to plugins (type-erased, thread-safe).
     pub vars: HashMap<String, Box<dyn Any + Send + Sync>>,
 }
@@ -101,11 +161,17 @@ impl Default for ProxyContext {
             route_params: None,
             upstream_override: None,
             peer: None,
+            inflight: None,
+            pooled_connection: None,
+            node_metadata: None,
             tries: 0,
+            failed_peers: Vec::new(),
             plugin: ProxyPluginExecutor::default_shared(),
             global_plugin: ProxyPluginExecutor::default_shared(),
             request_start: Instant::now(),
             request_id: None,
+            consumer: None,
+            consumer_plugins: None,
             vars: HashMap::new(),
         }
     }
@@ -175,6 +241,11 @@ impl ProxyContext {
     pub fn set_request_id(&mut self, id: String) {
         self.request_id = Some(id);
     }
+
+    /// Get the username of the authenticated consumer, if any.
+    pub fn consumer_name(&self) -> Option<&str> {
+        self.consumer.as_deref().map(|c| c.username.as_str())
+    }
 }
 
 // =============================================================================
@@ -395,6 +466,22 @@ impl ProxyPluginExecutor {
     pub fn default_shared() -> Arc<Self> {
         DEFAULT_PLUGIN_EXECUTOR.clone()
     }
+
+    /// Merges consumer group plugins into this executor.
+    ///
+    /// Consumer group plugins replace plugins of the same name, so they take
+    /// precedence over route, plugin config and service plugins.
+    pub fn merge_consumer_plugins(&self, consumer: &ProxyPluginExecutor) -> Self {
+        let mut plugins: Vec<Arc<dyn ProxyPlugin>> = self
+            .plugins
+            .iter()
+            .filter(|p| !consumer.plugins.iter().any(|c| c.name() == p.name()))
+            .cloned()
+            .chain(consumer.plugins.iter().cloned())
+            .collect();
+        sort_plugins_by_priority_desc(&mut plugins);
+        ProxyPluginExecutor { plugins }
+    }
 }
 
 #[async_trait]
@@ -408,10 +495,37 @@ impl ProxyPlugin for ProxyPluginExecutor {
     }
 
     async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
-        for plugin in self.plugins.iter() {
+        for (i, plugin) in self.plugins.iter().enumerate() {
             if plugin.request_filter(session, ctx).await? {
                 return Ok(true);
             }
+
+            // An auth plugin identified a consumer whose group carries plugins. Merge them
+            // into the route executor so every later phase sees them.
+            let Some(consumer_plugins) = ctx.consumer_plugins.take() else {
+                continue;
+            };
+            let is_route_executor = std::ptr::eq(self, Arc::as_ptr(&ctx.plugin));
+            let merged = Arc::new(ctx.plugin.merge_consumer_plugins(&consumer_plugins));
+            ctx.plugin = merged.clone();
+
+            // Global executors stop here: the merged route executor runs after them.
+            if !is_route_executor {
+                continue;
+            }
+
+            // Continue with the merged plugins that have not run yet.
+            let executed = &self.plugins[..=i];
+            for plugin in merged
+                .plugins
+                .iter()
+                .filter(|p| !executed.iter().any(|e| e.name() == p.name()))
+            {
+                if plugin.request_filter(session, ctx).await? {
+                    return Ok(true);
+                }
+            }
+            return Ok(false);
         }
         Ok(false)
     }
@@ -624,4 +738,128 @@ mod tests {
 
         assert_eq!(result, "/123-9");
     }
+
+    struct DummyPlugin {
+        name: &'static str,
+        priority: i32,
+    }
+
+    #[async_trait]
+    impl ProxyPlugin for DummyPlugin {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+    }
+
+    fn dummy(name: &'static str, priority: i32) -> Arc<dyn ProxyPlugin> {
+        Arc::new(DummyPlugin { name, priority })
+    }
+
+    #[test]
+    fn test_merge_consumer_plugins_overrides_by_name() {
+        let route = ProxyPluginExecutor {
+            plugins: vec![
+                dummy("key-auth", 2500),
+                dummy("limit-count", 1002),
+                dummy("echo", 100),
+            ],
+        };
+        let group_limit = dummy("limit-count", 1002);
+        let group = ProxyPluginExecutor {
+            plugins: vec![dummy("cors", 4000), group_limit.clone()],
+        };
+
+        let merged = route.merge_consumer_plugins(&group);
+        let names: Vec<&str> = merged.plugins.iter().map(|p| p.name()).collect();
+        assert_eq!(names, ["cors", "key-auth", "limit-count", "echo"]);
+        // Consumer group plugin replaces the route plugin with the same name
+        assert!(Arc::ptr_eq(&merged.plugins[2], &group_limit));
+    }
+
+    /// Plugin that records its request_filter run, and optionally acts as an auth plugin
+    /// identifying a consumer whose group carries `group` plugins.
+    struct TracedPlugin {
+        name: &'static str,
+        label: &'static str,
+        priority: i32,
+        group: Option<Arc<ProxyPluginExecutor>>,
+    }
+
+    #[async_trait]
+    impl ProxyPlugin for TracedPlugin {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+
+        async fn request_filter(&self, _: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
+            let mut trace = ctx.get::<Vec<&str>>("trace").cloned().unwrap_or_default();
+            trace.push(self.label);
+            ctx.set("trace", trace);
+            ctx.consumer_plugins = self.group.clone();
+            Ok(false)
+        }
+    }
+
+    fn traced(name: &'static str, label: &'static str, priority: i32) -> Arc<dyn ProxyPlugin> {
+        Arc::new(TracedPlugin {
+            name,
+            label,
+            priority,
+            group: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_request_filter_merges_consumer_plugins() {
+        let group = Arc::new(ProxyPluginExecutor {
+            plugins: vec![
+                traced("ip-restriction", "group-ip-restriction", 3000),
+                traced("limit-count", "group-limit-count", 1002),
+                traced("echo", "group-echo", 100),
+            ],
+        });
+        let route = Arc::new(ProxyPluginExecutor {
+            plugins: vec![
+                traced("ip-restriction", "route-ip-restriction", 3000),
+                Arc::new(TracedPlugin {
+                    name: "key-auth",
+                    label: "key-auth",
+                    priority: 2500,
+                    group: Some(group),
+                }),
+                traced("limit-count", "route-limit-count", 1002),
+            ],
+        });
+
+        let (_, stream) = tokio::io::duplex(1024);
+        let mut session = Session::new_h1(Box::new(stream));
+        let mut ctx = ProxyContext {
+            plugin: route.clone(),
+            ..Default::default()
+        };
+        assert!(!route.request_filter(&mut session, &mut ctx).await.unwrap());
+
+        // The group plugin replaces the route plugin that has not run yet, and a group
+        // plugin whose route namesake already ran is skipped
+        assert_eq!(
+            ctx.get::<Vec<&str>>("trace").unwrap(),
+            &[
+                "route-ip-restriction",
+                "key-auth",
+                "group-limit-count",
+                "group-echo"
+            ]
+        );
+        assert!(ctx.consumer_plugins.is_none());
+        let names: Vec<&str> = ctx.plugin.plugins.iter().map(|p| p.name()).collect();
+        assert_eq!(names, ["ip-restriction", "key-auth", "limit-count", "echo"]);
+    }
 }
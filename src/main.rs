@@ -22,14 +22,22 @@ use admin::AdminHttpApp;
 use config::{etcd::EtcdConfigSync, Config};
 use logging::Logger;
 use proxy::{
+    consumer::load_static_consumers,
+    consumer_group::load_static_consumer_groups,
     event::ProxyEventHandler,
     global_rule::load_static_global_rules,
+    plugin_config::load_static_plugin_configs,
     route::load_static_routes,
     service::load_static_services,
     ssl::{load_static_ssls, DynamicCert},
+    stream_route::load_static_stream_routes,
     upstream::{load_static_upstreams, SHARED_HEALTH_CHECK_SERVICE},
 };
-use service::{http::HttpService, status::StatusHttpApp};
+use service::{
+    http::HttpService,
+    status::StatusHttpApp,
+    stream::{StreamTcpApp, StreamUdpService},
+};
 
 // Service name constants
 const PINGSIX_SERVICE: &str = "pingsix";
@@ -116,6 +124,11 @@ fn main() {
     log::debug!("Server bootstrapped, adding services");
     pingsix_server.add_service(http_service);
 
+    // L4 proxy for stream_routes, independent of the HTTP listeners
+    if let Some(stream_cfg) = &config.pingsix.stream_proxy {
+        add_stream_services(&mut pingsix_server, stream_cfg);
+    }
+
     log::info!("Pingsix server running");
     pingsix_server.run_forever();
 }
@@ -169,19 +182,38 @@ fn add_listeners(
 
 /// Loads all static configurations from YAML config file.
 ///
-/// This includes SSLs, upstreams, services, global rules, and routes.
+/// This includes SSLs, upstreams, services, global rules, plugin configs, consumer groups,
+/// consumers, routes, and stream routes.
 /// All configurations must load successfully or the function returns an error.
 fn load_static_configurations(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     load_static_ssls(config)?;
     load_static_upstreams(config)?;
     load_static_services(config)?;
     load_static_global_rules(config)?;
+    load_static_plugin_configs(config)?;
+    load_static_consumer_groups(config)?;
+    load_static_consumers(config)?;
     load_static_routes(config)?;
+    load_static_stream_routes(config)?;
 
     log::info!("All static configurations loaded successfully");
     Ok(())
 }
 
+/// Adds TCP and UDP stream proxy services for the configured stream listeners.
+fn add_stream_services(server: &mut Server, cfg: &config::StreamProxy) {
+    if !cfg.tcp.is_empty() {
+        log::debug!("Configuring stream TCP listeners");
+        server.add_service(StreamTcpApp::stream_tcp_service(cfg));
+        log::info!("Stream TCP proxy enabled on {:?}", cfg.tcp);
+    }
+    if !cfg.udp.is_empty() {
+        log::debug!("Configuring stream UDP listeners");
+        server.add_service(StreamUdpService::new(cfg));
+        log::info!("Stream UDP proxy enabled on {:?}", cfg.udp);
+    }
+}
+
 /// Conditionally enables monitoring and admin services based on configuration.
 ///
 /// Sentry integration requires valid DSN to prevent silent failures in production.
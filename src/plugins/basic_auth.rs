@@ -7,10 +7,11 @@ use pingora_error::Result;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 use crate::{
     core::{constant_time_eq, ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    proxy::consumer::consumer_fetch_by_credential,
     utils::{request, response::ResponseBuilder},
 };
 
@@ -18,21 +19,34 @@ pub const PLUGIN_NAME: &str = "basic-auth";
 const PRIORITY: i32 = 2520;
 
 /// Creates a Basic Auth plugin instance.
+///
+/// Without inline `username`/`password`, credentials are checked against consumer
+/// `basic-auth` credentials.
 pub fn create_basic_auth_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
     Ok(Arc::new(PluginBasicAuth { config }))
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "PluginConfig::validate_credentials_pair"))]
 struct PluginConfig {
     #[validate(length(min = 1))]
-    username: String,
+    username: Option<String>,
     #[validate(length(min = 1))]
-    password: String,
+    password: Option<String>,
     #[serde(default)]
     hide_credentials: bool,
 }
 
+impl PluginConfig {
+    fn validate_credentials_pair(&self) -> Result<(), ValidationError> {
+        if self.username.is_some() != self.password.is_some() {
+            return Err(ValidationError::new("username_and_password_required"));
+        }
+        Ok(())
+    }
+}
+
 impl TryFrom<JsonValue> for PluginConfig {
     type Error = ProxyError;
 
@@ -50,37 +64,62 @@ pub struct PluginBasicAuth {
 }
 
 impl PluginBasicAuth {
-    /// Validates Basic Authentication credentials using constant-time comparison.
+    /// Decodes a Basic Authorization header value into `(username, password)`.
     ///
     /// This method:
     /// 1. Checks for "Basic " prefix (case-insensitive)
     /// 2. Decodes the Base64-encoded credentials
     /// 3. Splits username and password at the first colon
-    /// 4. Uses constant-time comparison to prevent timing attacks
-    fn validate_credentials(&self, auth_value: &str) -> bool {
+    fn decode_credentials(auth_value: &str) -> Option<(String, String)> {
         // 1. Check prefix
         if !auth_value.to_lowercase().starts_with("basic ") {
-            return false;
+            return None;
         }
 
         // 2. Decode Base64
         let credential_part = &auth_value[6..];
-        let Ok(decoded_bytes) = general_purpose::STANDARD.decode(credential_part) else {
+        let decoded_bytes = general_purpose::STANDARD.decode(credential_part).ok()?;
+        let decoded_str = String::from_utf8(decoded_bytes).ok()?;
+
+        // 3. Separate username:password
+        let (user, pass) = decoded_str.split_once(':')?;
+        Some((user.to_string(), pass.to_string()))
+    }
+
+    /// Validates Basic Authentication credentials against the inline username and
+    /// password using constant-time comparison to prevent timing attacks.
+    fn validate_credentials(&self, auth_value: &str) -> bool {
+        let (Some(username), Some(password)) = (&self.config.username, &self.config.password)
+        else {
             return false;
         };
-
-        let Ok(decoded_str) = String::from_utf8(decoded_bytes) else {
+        let Some((user, pass)) = Self::decode_credentials(auth_value) else {
             return false;
         };
 
-        // 3. Separate username:password
-        let Some((user, pass)) = decoded_str.split_once(':') else {
+        constant_time_eq(&user, username) && constant_time_eq(&pass, password)
+    }
+
+    /// Authenticates against consumer `basic-auth` credentials and records the consumer.
+    fn authenticate_consumer(auth_value: &str, ctx: &mut ProxyContext) -> bool {
+        let Some((user, pass)) = Self::decode_credentials(auth_value) else {
+            return false;
+        };
+        let Some(consumer) = consumer_fetch_by_credential(PLUGIN_NAME, &user) else {
             return false;
         };
 
-        // 4. Use constant-time comparison to prevent timing attacks
-        constant_time_eq(user, &self.config.username)
-            && constant_time_eq(pass, &self.config.password)
+        let password = consumer
+            .credential(PLUGIN_NAME)
+            .and_then(|c| c.get("password"))
+            .and_then(JsonValue::as_str)
+            .unwrap_or_default();
+        if password.is_empty() || !constant_time_eq(&pass, password) {
+            return false;
+        }
+
+        consumer.attach(ctx);
+        true
     }
 }
 
@@ -94,12 +133,13 @@ impl ProxyPlugin for PluginBasicAuth {
         PRIORITY
     }
 
-    async fn request_filter(&self, session: &mut Session, _ctx: &mut ProxyContext) -> Result<bool> {
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
         let auth_header =
             request::get_req_header_value(session.req_header(), header::AUTHORIZATION.as_str());
 
         let is_valid = match auth_header {
-            Some(val) => self.validate_credentials(val),
+            Some(val) if self.config.username.is_some() => self.validate_credentials(val),
+            Some(val) => Self::authenticate_consumer(val, ctx),
             None => false,
         };
 
@@ -133,8 +173,8 @@ mod tests {
     fn build_plugin(username: &str, password: &str) -> PluginBasicAuth {
         PluginBasicAuth {
             config: PluginConfig {
-                username: username.to_string(),
-                password: password.to_string(),
+                username: Some(username.to_string()),
+                password: Some(password.to_string()),
                 hide_credentials: false,
             },
         }
@@ -158,4 +198,10 @@ mod tests {
         let header = format!("Basic {}", general_purpose::STANDARD.encode("demo:badpass"));
         assert!(!plugin.validate_credentials(&header));
     }
+
+    #[test]
+    fn config_requires_username_and_password_together() {
+        assert!(PluginConfig::try_from(serde_json::json!({})).is_ok());
+        assert!(PluginConfig::try_from(serde_json::json!({"username": "demo"})).is_err());
+    }
 }
@@ -31,7 +31,9 @@ struct PluginConfig {
     /// The log format string, containing static text and variables (e.g., `$remote_addr "$request_method $uri" $status`).
     /// Supported variables include: `request_method`, `uri`, `query_string`, `http_host`, `request_time`,
     /// `http_user_agent`, `http_referer`, `remote_addr`, `remote_port`, `server_addr`, `status`,
-    /// `server_protocol`, `request_id`, `body_bytes_sent`, `error`, and custom variables via `var_<name>`.
+    /// `server_protocol`, `request_id`, `consumer_name`, `body_bytes_sent`, `bytes_received`, `upgrade`,
+    /// `error`, custom variables via `var_<name>`, and upstream node metadata via
+    /// `upstream_metadata_<key>`. For upgraded connections, `request_time` is the connection duration.
     #[serde(default = "PluginConfig::default_log_format")]
     log_format: String,
 }
@@ -126,6 +128,7 @@ impl LogFormat {
             "status" => 4,                           // 3-4 bytes (e.g., "200")
             "request_method" => 8,                   // 3-7 bytes (e.g., "GET")
             "request_id" => 36,                      // UUID length
+            "consumer_name" => 16,                   // Consumer username
             "http_user_agent" => 128,                // Browser UA can be long
             "uri" => 64,                             // Average URI length
             "query_string" => 32,                    // Average query string length
@@ -137,6 +140,8 @@ impl LogFormat {
             "server_addr" => 16,                     // Server address
             "server_protocol" => 8,                  // "http/1.1" or "http/2"
             "body_bytes_sent" => 12,                 // Large numbers
+            "bytes_received" => 12,                  // Large numbers
+            "upgrade" => 9,                          // "websocket" or empty
             "error" => 128,                          // Error messages can be long
             _ if var_name.starts_with("var_") => 32, // Custom variables
             _ => 16,                                 // Default for unknown variables
@@ -178,6 +183,15 @@ impl LogFormat {
             return ctx.get_str(custom_var_name).unwrap_or("").to_string();
         }
 
+        if let Some(key) = var.strip_prefix("upstream_metadata_") {
+            return ctx
+                .node_metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get(key))
+                .unwrap_or_default()
+                .to_string();
+        }
+
         // Handle built-in variables
         match var {
             "request_method" => session.req_header().method.as_str().to_string(),
@@ -224,7 +238,18 @@ impl LogFormat {
                 }
             }
             "request_id" => ctx.request_id().unwrap_or("").to_string(),
+            "consumer_name" => ctx.consumer_name().unwrap_or("").to_string(),
             "body_bytes_sent" => session.body_bytes_sent().to_string(),
+            "bytes_received" => session.body_bytes_read().to_string(),
+            "upgrade" => {
+                if session.was_upgraded() {
+                    request::get_req_header_value(session.req_header(), "upgrade")
+                        .unwrap_or_default()
+                        .to_ascii_lowercase()
+                } else {
+                    "".to_string()
+                }
+            }
             "error" => e.map(|e| e.to_string()).unwrap_or_default(),
             _ => "".to_string(),
         }
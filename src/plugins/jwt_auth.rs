@@ -3,7 +3,7 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine as _};
 use http::StatusCode;
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{decode, errors::ErrorKind, Algorithm, DecodingKey, TokenData, Validation};
 use pingora_error::Result;
 use pingora_proxy::Session;
 use serde::{Deserialize, Serialize};
@@ -11,6 +11,7 @@ use serde_json::Value as JsonValue;
 
 use crate::{
     core::{ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    proxy::consumer::consumer_fetch_by_credential,
     utils::{request, response::ResponseBuilder},
 };
 
@@ -29,11 +30,17 @@ const DEFAULT_JWT_COOKIE: &str = "jwt";
 /// Creates a JWT Auth plugin instance with the given configuration.
 /// This plugin validates JWTs from HTTP headers, query parameters, or cookies, and optionally
 /// stores the JWT payload in the request context or hides credentials after validation.
+/// Without `secret` or `public_key`, tokens are verified with the credentials of the consumer
+/// whose `jwt-auth` key matches the token's `key` claim.
 pub fn create_jwt_auth_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
-    let decoding_key = config.get_decoding_key().map_err(|e| {
-        ProxyError::Configuration(format!("Failed to create JWT decoding key: {e}"))
-    })?;
+    let decoding_key = if config.secret.is_none() && config.public_key.is_none() {
+        None
+    } else {
+        Some(config.get_decoding_key().map_err(|e| {
+            ProxyError::Configuration(format!("Failed to create JWT decoding key: {e}"))
+        })?)
+    };
 
     // Pre-create validation object for better performance
     let mut validation = Validation::new(config.algorithm);
@@ -170,7 +177,8 @@ struct Claims {
 /// Validates JWTs and optionally stores payload or hides credentials.
 pub struct PluginJWTAuth {
     config: PluginConfig,
-    decoding_key: DecodingKey,
+    /// `None` when tokens are verified with consumer credentials.
+    decoding_key: Option<DecodingKey>,
     validation: Validation, // Pre-created for better performance
 }
 
@@ -203,20 +211,16 @@ impl ProxyPlugin for PluginJWTAuth {
             }
         };
 
-        // Parse JWT using pre-created validation
-        let token_data = match decode::<Claims>(&token, &self.decoding_key, &self.validation) {
+        // Parse JWT using pre-created validation, or the consumer named by the token
+        let verified = match &self.decoding_key {
+            Some(key) => {
+                decode::<Claims>(&token, key, &self.validation).map_err(|e| error_message(&e))
+            }
+            None => Self::decode_for_consumer(&token, ctx),
+        };
+        let token_data = match verified {
             Ok(data) => data,
-            Err(e) => {
-                let error_msg = match e.kind() {
-                    jsonwebtoken::errors::ErrorKind::InvalidToken => "Invalid token format",
-                    jsonwebtoken::errors::ErrorKind::InvalidSignature => "Invalid signature",
-                    jsonwebtoken::errors::ErrorKind::ExpiredSignature => "Token expired",
-                    jsonwebtoken::errors::ErrorKind::InvalidIssuer => "Invalid issuer",
-                    jsonwebtoken::errors::ErrorKind::InvalidAudience => "Invalid audience",
-                    jsonwebtoken::errors::ErrorKind::InvalidSubject => "Invalid subject",
-                    jsonwebtoken::errors::ErrorKind::ImmatureSignature => "Token not yet valid",
-                    _ => "Invalid token",
-                };
+            Err(error_msg) => {
                 ResponseBuilder::send_proxy_error(
                     session,
                     StatusCode::UNAUTHORIZED,
@@ -256,7 +260,58 @@ impl ProxyPlugin for PluginJWTAuth {
     }
 }
 
+/// Maps a JWT decoding error to the message returned to the client.
+fn error_message(e: &jsonwebtoken::errors::Error) -> &'static str {
+    match e.kind() {
+        ErrorKind::InvalidToken => "Invalid token format",
+        ErrorKind::InvalidSignature => "Invalid signature",
+        ErrorKind::ExpiredSignature => "Token expired",
+        ErrorKind::InvalidIssuer => "Invalid issuer",
+        ErrorKind::InvalidAudience => "Invalid audience",
+        ErrorKind::InvalidSubject => "Invalid subject",
+        ErrorKind::ImmatureSignature => "Token not yet valid",
+        _ => "Invalid token",
+    }
+}
+
 impl PluginJWTAuth {
+    /// Verifies `token` with the `jwt-auth` credentials of the consumer named by its
+    /// `key` claim, and records the consumer in the context on success.
+    fn decode_for_consumer(
+        token: &str,
+        ctx: &mut ProxyContext,
+    ) -> std::result::Result<TokenData<Claims>, &'static str> {
+        let consumer_key = Self::unverified_consumer_key(token).ok_or("Missing consumer key")?;
+        let consumer = consumer_fetch_by_credential(PLUGIN_NAME, &consumer_key)
+            .ok_or("Invalid consumer key")?;
+
+        let credential = consumer
+            .credential(PLUGIN_NAME)
+            .cloned()
+            .ok_or("Invalid consumer key")?;
+        let config =
+            PluginConfig::try_from(credential).map_err(|_| "Invalid consumer credential")?;
+        let decoding_key = config
+            .get_decoding_key()
+            .map_err(|_| "Invalid consumer credential")?;
+        let mut validation = Validation::new(config.algorithm);
+        validation.leeway = config.lifetime_grace_period;
+
+        let token_data =
+            decode::<Claims>(token, &decoding_key, &validation).map_err(|e| error_message(&e))?;
+        consumer.attach(ctx);
+        Ok(token_data)
+    }
+
+    /// Reads the `key` claim without verifying the signature; the key only selects
+    /// which consumer's credentials the token is verified with.
+    fn unverified_consumer_key(token: &str) -> Option<String> {
+        let payload = token.split('.').nth(1)?;
+        let bytes = general_purpose::URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let claims: JsonValue = serde_json::from_slice(&bytes).ok()?;
+        claims.get("key")?.as_str().map(str::to_string)
+    }
+
     /// Extracts JWT from header, query, or cookie using a cleaner chain approach
     fn extract_token(&self, session: &mut Session, ctx: &mut ProxyContext) -> Option<String> {
         self.extract_from_header(session)
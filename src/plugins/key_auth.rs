@@ -10,6 +10,7 @@ use validator::Validate;
 
 use crate::{
     core::{constant_time_eq, ProxyContext, ProxyError, ProxyPlugin, ProxyResult},
+    proxy::consumer::consumer_fetch_by_credential,
     utils::{request, response::ResponseBuilder},
 };
 
@@ -23,7 +24,8 @@ const DEFAULT_API_KEY_QUERY: &str = "apikey";
 
 /// Creates a Key Auth plugin instance with the given configuration.
 /// This plugin authenticates requests by matching an API key in the HTTP header or query parameter
-/// against configured keys. If the key is invalid or missing, it returns a `401 Unauthorized` response.
+/// against configured keys, or against consumer `key-auth` credentials when no keys are configured.
+/// If the key is invalid or missing, it returns a `401 Unauthorized` response.
 pub fn create_key_auth_plugin(cfg: JsonValue) -> ProxyResult<Arc<dyn ProxyPlugin>> {
     let config = PluginConfig::try_from(cfg)?;
     Ok(Arc::new(PluginKeyAuth { config }))
@@ -48,7 +50,7 @@ struct PluginConfig {
 
     /// Multiple API keys to match against. Supports key rotation.
     /// Takes precedence over single `key` if both are provided.
-    #[validate(length(min = 1))]
+    /// When neither `key` nor `keys` is set, keys are looked up from consumers.
     #[serde(default)]
     keys: Vec<String>,
 
@@ -70,6 +72,11 @@ impl PluginConfig {
         false
     }
 
+    /// Whether keys are configured on the plugin itself rather than on consumers.
+    fn has_inline_keys(&self) -> bool {
+        !self.keys.is_empty() || self.key.is_some()
+    }
+
     /// Get all valid keys (combines single key and multiple keys)
     fn get_valid_keys(&self) -> Vec<&String> {
         if !self.keys.is_empty() {
@@ -123,7 +130,7 @@ impl ProxyPlugin for PluginKeyAuth {
         PRIORITY
     }
 
-    async fn request_filter(&self, session: &mut Session, _ctx: &mut ProxyContext) -> Result<bool> {
+    async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
         // Try to extract key from header or query
         let (value, source) =
             request::get_req_header_value(session.req_header(), &self.config.header)
@@ -134,8 +141,22 @@ impl ProxyPlugin for PluginKeyAuth {
                 })
                 .unwrap_or(("", KeySource::None));
 
-        // Validate key using constant-time comparison
-        if value.is_empty() || !self.is_valid_key(value) {
+        // Inline keys use constant-time comparison; otherwise the key identifies a consumer
+        let authorized = if value.is_empty() {
+            false
+        } else if !self.config.has_inline_keys() {
+            match consumer_fetch_by_credential(PLUGIN_NAME, value) {
+                Some(consumer) => {
+                    consumer.attach(ctx);
+                    true
+                }
+                None => false,
+            }
+        } else {
+            self.is_valid_key(value)
+        };
+
+        if !authorized {
             ResponseBuilder::send_proxy_error(
                 session,
                 StatusCode::UNAUTHORIZED,
@@ -37,6 +37,7 @@ struct PluginConfig {
     key_type: UpstreamHashOn,
 
     /// Key name or value to use for rate limiting (e.g., header name for `HEADER`, variable name for `VARS`).
+    /// The `consumer_name` variable limits per authenticated consumer.
     /// Must be non-empty and valid for the specified `key_type`.
     #[validate(custom(function = "validate_key"))]
     key: String,
@@ -140,7 +141,13 @@ impl ProxyPlugin for PluginRateLimit {
     }
 
     async fn request_filter(&self, session: &mut Session, ctx: &mut ProxyContext) -> Result<bool> {
-        let key = request_selector_key(session, &self.config.key_type, self.config.key.as_str());
+        // `consumer_name` is resolved from the consumer identified by an auth plugin
+        let key = match (&self.config.key_type, self.config.key.as_str()) {
+            (UpstreamHashOn::VARS, "consumer_name") => {
+                ctx.consumer_name().unwrap_or_default().to_string()
+            }
+            _ => request_selector_key(session, &self.config.key_type, self.config.key.as_str()),
+        };
 
         // Handle empty key based on policy
         if key.is_empty() {
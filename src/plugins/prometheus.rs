@@ -107,6 +107,43 @@ static RESPONSE_SIZE: Lazy<HistogramVec> = Lazy::new(|| {
         .expect("Failed to register prometheus metric: http_response_size_bytes")
 });
 
+// Upgraded (WebSocket) connections, kept out of the request latency and size metrics
+static UPGRADED_CONNECTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "upgraded_connections_total",
+        "Total upgraded (WebSocket) connections per route in pingsix",
+        &["route", "service"]
+    )
+    .expect("Failed to register prometheus metric: upgraded_connections_total")
+});
+
+// Histogram for upgraded connection duration
+static UPGRADED_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    let opts = HistogramOpts::new(
+        "upgraded_connection_duration_seconds",
+        "Upgraded (WebSocket) connection duration in seconds per route in pingsix",
+    )
+    .buckets(vec![
+        1.0, 10.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 21600.0, 86400.0,
+    ]);
+    register_histogram_vec!(opts, &["route", "service", "node"])
+        .expect("Failed to register prometheus metric: upgraded_connection_duration_seconds")
+});
+
+// Bytes exchanged over upgraded connections
+static UPGRADED_BANDWIDTH: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "upgraded_bandwidth",
+        "Total bytes exchanged over upgraded (WebSocket) connections in pingsix",
+        &[
+            "type",    // ingress/egress
+            "route",   // Route ID
+            "service", // Service ID
+        ]
+    )
+    .expect("Failed to register prometheus metric: upgraded_bandwidth")
+});
+
 pub const PLUGIN_NAME: &str = "prometheus";
 const PRIORITY: i32 = 500;
 
@@ -214,11 +251,23 @@ impl ProxyPlugin for PluginPrometheus {
             .with_label_values(&[code, route_id, &path_template, host, service, &node])
             .inc();
 
-        // Record request latency
-        let elapsed_ms = ctx.elapsed_ms_f64();
-        LATENCY
-            .with_label_values(&["request", route_id, service, &node])
-            .observe(elapsed_ms);
+        // Upgraded connections live as long as the client keeps them open, so their
+        // duration and bytes are recorded separately from request latency and sizes
+        if session.was_upgraded() {
+            UPGRADED_CONNECTIONS
+                .with_label_values(&[route_id, service])
+                .inc();
+            UPGRADED_DURATION
+                .with_label_values(&[route_id, service, &node])
+                .observe(ctx.elapsed_ms_f64() / 1000.0);
+            UPGRADED_BANDWIDTH
+                .with_label_values(&["ingress", route_id, service])
+                .inc_by(session.body_bytes_read() as _);
+            UPGRADED_BANDWIDTH
+                .with_label_values(&["egress", route_id, service])
+                .inc_by(session.body_bytes_sent() as _);
+            return;
+        }
 
         // Record bandwidth metrics
         BANDWIDTH
@@ -229,6 +278,12 @@ impl ProxyPlugin for PluginPrometheus {
             .with_label_values(&["egress", route_id, service, &node])
             .inc_by(session.body_bytes_sent() as _);
 
+        // Record request latency
+        let elapsed_ms = ctx.elapsed_ms_f64();
+        LATENCY
+            .with_label_values(&["request", route_id, service, &node])
+            .observe(elapsed_ms);
+
         // Record request and response sizes
         REQUEST_SIZE
             .with_label_values(&[route_id, service])
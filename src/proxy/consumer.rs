@@ -0,0 +1,289 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    config::{self, Identifiable},
+    core::{
+        sort_plugins_by_priority_desc, ProxyContext, ProxyError, ProxyPluginExecutor, ProxyResult,
+    },
+    plugins::{basic_auth, build_plugin, jwt_auth, key_auth},
+};
+
+use super::{consumer_group::consumer_group_fetch, MapOperations};
+
+/// Auth plugins that identify consumers, with the credential field used for lookup.
+const CREDENTIAL_FIELDS: [(&str, &str); 3] = [
+    (key_auth::PLUGIN_NAME, "key"),
+    (basic_auth::PLUGIN_NAME, "username"),
+    (jwt_auth::PLUGIN_NAME, "key"),
+];
+
+/// Represents a consumer resource.
+pub struct ProxyConsumer {
+    pub inner: Arc<config::Consumer>,
+    /// The consumer's own plugins besides its auth credentials, if any.
+    pub executor: Option<Arc<ProxyPluginExecutor>>,
+}
+
+impl Identifiable for ProxyConsumer {
+    fn id(&self) -> &str {
+        &self.inner.username
+    }
+
+    fn set_id(&mut self, id: String) {
+        Arc::make_mut(&mut self.inner).username = id;
+    }
+}
+
+impl ProxyConsumer {
+    pub fn new(consumer: config::Consumer) -> ProxyResult<Self> {
+        for (plugin, field) in CREDENTIAL_FIELDS {
+            if let Some(conf) = consumer.plugins.get(plugin) {
+                if credential_value(conf, field).is_none() {
+                    return Err(ProxyError::Configuration(format!(
+                        "Consumer '{}' plugin '{}' requires a non-empty '{}'",
+                        consumer.username, plugin, field
+                    )));
+                }
+            }
+        }
+
+        // Auth plugin entries are credentials; the others run for the consumer's requests
+        let mut plugins = Vec::new();
+        for (name, value) in consumer.plugins.iter() {
+            if CREDENTIAL_FIELDS.iter().any(|(plugin, _)| plugin == name) {
+                continue;
+            }
+            let plugin = build_plugin(name, value.clone()).map_err(|e| {
+                ProxyError::Plugin(format!(
+                    "Failed to build plugin '{}' for consumer '{}': {}",
+                    name, consumer.username, e
+                ))
+            })?;
+            plugins.push(plugin);
+        }
+        sort_plugins_by_priority_desc(plugins.as_mut_slice());
+
+        Ok(ProxyConsumer {
+            inner: Arc::new(consumer),
+            executor: (!plugins.is_empty()).then(|| Arc::new(ProxyPluginExecutor { plugins })),
+        })
+    }
+
+    /// Returns the consumer's configuration for the given auth plugin.
+    pub fn credential(&self, plugin: &str) -> Option<&JsonValue> {
+        self.inner.plugins.get(plugin)
+    }
+
+    /// Records this consumer as authenticated for the request, queueing its own and its
+    /// group's plugins to be merged into the route executor.
+    ///
+    /// The consumer's plugins replace its group's plugins of the same name.
+    pub fn attach(&self, ctx: &mut ProxyContext) {
+        ctx.consumer = Some(self.inner.clone());
+        let group = self
+            .inner
+            .group_id
+            .as_deref()
+            .and_then(consumer_group_fetch)
+            .map(|group| group.executor.clone());
+        ctx.consumer_plugins = match (group, &self.executor) {
+            (Some(group), Some(own)) => Some(Arc::new(group.merge_consumer_plugins(own))),
+            (group, own) => group.or_else(|| own.clone()),
+        };
+    }
+}
+
+fn credential_value<'a>(conf: &'a JsonValue, field: &str) -> Option<&'a str> {
+    conf.get(field)
+        .and_then(JsonValue::as_str)
+        .filter(|v| !v.is_empty())
+}
+
+/// Global map to store consumers by username, initialized lazily.
+pub static CONSUMER_MAP: Lazy<DashMap<String, Arc<ProxyConsumer>>> = Lazy::new(DashMap::new);
+
+/// Credential lookup tables: plugin name -> credential value -> consumer.
+type CredentialTables = HashMap<&'static str, HashMap<String, Arc<ProxyConsumer>>>;
+
+static CONSUMER_CREDENTIALS: Lazy<ArcSwap<CredentialTables>> =
+    Lazy::new(|| ArcSwap::new(Arc::new(HashMap::new())));
+
+/// Finds the consumer owning `credential` for the given auth plugin.
+pub fn consumer_fetch_by_credential(plugin: &str, credential: &str) -> Option<Arc<ProxyConsumer>> {
+    let consumer = CONSUMER_CREDENTIALS
+        .load()
+        .get(plugin)
+        .and_then(|credentials| credentials.get(credential))
+        .cloned();
+    if consumer.is_none() {
+        log::debug!("No consumer found for plugin '{plugin}' credential");
+    }
+    consumer
+}
+
+/// Rebuilds the credential lookup tables from `CONSUMER_MAP`.
+///
+/// Credentials must be unique per plugin; duplicates are logged and the
+/// lexicographically first consumer wins.
+pub fn reload_consumer_credentials() {
+    let mut consumers: Vec<Arc<ProxyConsumer>> =
+        CONSUMER_MAP.iter().map(|e| e.value().clone()).collect();
+    consumers.sort_by(|a, b| a.inner.username.cmp(&b.inner.username));
+
+    let mut tables = CredentialTables::new();
+    for consumer in consumers {
+        for (plugin, field) in CREDENTIAL_FIELDS {
+            let Some(value) = consumer
+                .credential(plugin)
+                .and_then(|c| credential_value(c, field))
+            else {
+                continue;
+            };
+
+            let table = tables.entry(plugin).or_default();
+            if let Some(existing) = table.get(value) {
+                log::error!(
+                    "Duplicate {} credential on consumers '{}' and '{}', keeping '{}'",
+                    plugin,
+                    existing.inner.username,
+                    consumer.inner.username,
+                    existing.inner.username
+                );
+                continue;
+            }
+            table.insert(value.to_string(), consumer.clone());
+        }
+    }
+
+    CONSUMER_CREDENTIALS.store(Arc::new(tables));
+}
+
+/// Loads consumers from the given configuration.
+pub fn load_static_consumers(config: &config::Config) -> ProxyResult<()> {
+    let proxy_consumers: Vec<Arc<ProxyConsumer>> = config
+        .consumers
+        .iter()
+        .map(|consumer| {
+            log::info!("Configuring Consumer: {}", consumer.username);
+            match ProxyConsumer::new(consumer.clone()) {
+                Ok(proxy_consumer) => Ok(Arc::new(proxy_consumer)),
+                Err(e) => {
+                    log::error!("Failed to configure Consumer {}: {}", consumer.username, e);
+                    Err(e)
+                }
+            }
+        })
+        .collect::<ProxyResult<Vec<_>>>()?;
+
+    CONSUMER_MAP.reload_resources(proxy_consumers);
+    reload_consumer_credentials();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn consumer(username: &str, plugins: JsonValue) -> config::Consumer {
+        config::Consumer {
+            username: username.to_string(),
+            labels: HashMap::new(),
+            plugins: serde_json::from_value(plugins).unwrap(),
+            group_id: None,
+        }
+    }
+
+    #[test]
+    fn test_consumer_requires_credential_field() {
+        assert!(ProxyConsumer::new(consumer("a", json!({"key-auth": {"key": "k"}}))).is_ok());
+        assert!(ProxyConsumer::new(consumer("a", json!({"key-auth": {}}))).is_err());
+        assert!(
+            ProxyConsumer::new(consumer("a", json!({"basic-auth": {"password": "p"}}))).is_err()
+        );
+    }
+
+    #[test]
+    fn test_consumer_plugins() {
+        let proxy_consumer = ProxyConsumer::new(consumer(
+            "a",
+            json!({"key-auth": {"key": "k"}, "echo": {"body": "consumer"}}),
+        ))
+        .unwrap();
+        // Credentials are not plugins to run
+        let executor = proxy_consumer.executor.as_ref().unwrap();
+        let names: Vec<&str> = executor.plugins.iter().map(|p| p.name()).collect();
+        assert_eq!(names, ["echo"]);
+
+        assert!(
+            ProxyConsumer::new(consumer("a", json!({"key-auth": {"key": "k"}})))
+                .unwrap()
+                .executor
+                .is_none()
+        );
+        // Invalid plugin configs are rejected like on routes
+        assert!(ProxyConsumer::new(consumer("a", json!({"echo": {}}))).is_err());
+        assert!(ProxyConsumer::new(consumer("a", json!({"no-such-plugin": {}}))).is_err());
+    }
+
+    #[test]
+    fn test_consumer_plugins_override_group() {
+        use crate::proxy::consumer_group::{ProxyConsumerGroup, CONSUMER_GROUP_MAP};
+
+        let group = ProxyConsumerGroup::new_with_plugins(config::ConsumerGroup {
+            id: "test-attach-group".to_string(),
+            plugins: serde_json::from_value(json!({
+                "echo": {"body": "group"},
+                "cors": {},
+            }))
+            .unwrap(),
+            ..Default::default()
+        })
+        .unwrap();
+        CONSUMER_GROUP_MAP.insert_resource(Arc::new(group));
+
+        let mut conf = consumer("a", json!({"echo": {"body": "consumer"}}));
+        conf.group_id = Some("test-attach-group".to_string());
+        let proxy_consumer = ProxyConsumer::new(conf).unwrap();
+
+        let mut ctx = ProxyContext::default();
+        proxy_consumer.attach(&mut ctx);
+        let plugins = &ctx.consumer_plugins.as_ref().unwrap().plugins;
+        let names: Vec<&str> = plugins.iter().map(|p| p.name()).collect();
+        assert_eq!(names, ["cors", "echo"]);
+        let own = &proxy_consumer.executor.as_ref().unwrap().plugins[0];
+        assert!(Arc::ptr_eq(&plugins[1], own));
+
+        CONSUMER_GROUP_MAP.remove("test-attach-group");
+    }
+
+    #[test]
+    fn test_credential_lookup() {
+        for c in [
+            consumer(
+                "test-lookup-a",
+                json!({"key-auth": {"key": "test-lookup-key"}}),
+            ),
+            consumer(
+                "test-lookup-b",
+                json!({"basic-auth": {"username": "test-lookup-user", "password": "p"}}),
+            ),
+        ] {
+            CONSUMER_MAP.insert_resource(Arc::new(ProxyConsumer::new(c).unwrap()));
+        }
+        reload_consumer_credentials();
+
+        let found = consumer_fetch_by_credential(key_auth::PLUGIN_NAME, "test-lookup-key");
+        assert_eq!(found.unwrap().inner.username, "test-lookup-a");
+        let found = consumer_fetch_by_credential(basic_auth::PLUGIN_NAME, "test-lookup-user");
+        assert_eq!(found.unwrap().inner.username, "test-lookup-b");
+        assert!(consumer_fetch_by_credential(jwt_auth::PLUGIN_NAME, "test-lookup-key").is_none());
+    }
+}
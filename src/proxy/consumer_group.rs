@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+use crate::{
+    config::{self, Identifiable},
+    core::{sort_plugins_by_priority_desc, ProxyError, ProxyPluginExecutor, ProxyResult},
+    plugins::build_plugin,
+};
+
+use super::MapOperations;
+
+/// Fetches a consumer group by its ID.
+pub fn consumer_group_fetch(id: &str) -> Option<Arc<ProxyConsumerGroup>> {
+    match CONSUMER_GROUP_MAP.get(id) {
+        Some(consumer_group) => Some(consumer_group.value().clone()),
+        None => {
+            log::debug!("Consumer group '{id}' not found in cache");
+            None
+        }
+    }
+}
+
+/// Represents a consumer group whose plugins apply to its authenticated consumers.
+pub struct ProxyConsumerGroup {
+    pub inner: config::ConsumerGroup,
+    pub executor: Arc<ProxyPluginExecutor>,
+}
+
+impl Identifiable for ProxyConsumerGroup {
+    fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.inner.id = id;
+    }
+}
+
+impl ProxyConsumerGroup {
+    pub fn new_with_plugins(consumer_group: config::ConsumerGroup) -> ProxyResult<Self> {
+        let mut plugins = Vec::with_capacity(consumer_group.plugins.len());
+        for (name, value) in consumer_group.plugins.iter() {
+            let plugin = build_plugin(name, value.clone()).map_err(|e| {
+                ProxyError::Plugin(format!(
+                    "Failed to build plugin '{}' for consumer group '{}': {}",
+                    name, consumer_group.id, e
+                ))
+            })?;
+            plugins.push(plugin);
+        }
+
+        // Pre-sort plugins once at build-time to avoid per-request sorting in consumer merges.
+        sort_plugins_by_priority_desc(plugins.as_mut_slice());
+
+        Ok(ProxyConsumerGroup {
+            inner: consumer_group,
+            executor: Arc::new(ProxyPluginExecutor { plugins }),
+        })
+    }
+}
+
+/// Global map to store consumer groups, initialized lazily.
+pub static CONSUMER_GROUP_MAP: Lazy<DashMap<String, Arc<ProxyConsumerGroup>>> =
+    Lazy::new(DashMap::new);
+
+/// Loads consumer groups from the given configuration.
+pub fn load_static_consumer_groups(config: &config::Config) -> ProxyResult<()> {
+    let proxy_consumer_groups: Vec<Arc<ProxyConsumerGroup>> = config
+        .consumer_groups
+        .iter()
+        .map(|consumer_group| {
+            log::info!("Configuring ConsumerGroup: {}", consumer_group.id);
+            match ProxyConsumerGroup::new_with_plugins(consumer_group.clone()) {
+                Ok(proxy_consumer_group) => Ok(Arc::new(proxy_consumer_group)),
+                Err(e) => {
+                    log::error!(
+                        "Failed to configure ConsumerGroup {}: {}",
+                        consumer_group.id,
+                        e
+                    );
+                    Err(e)
+                }
+            }
+        })
+        .collect::<ProxyResult<Vec<_>>>()?;
+
+    CONSUMER_GROUP_MAP.reload_resources(proxy_consumer_groups);
+
+    Ok(())
+}
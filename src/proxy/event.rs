@@ -5,16 +5,24 @@ use etcd_client::{Event, GetResponse};
 use crate::{
     config::{
         etcd::{json_to_resource, EtcdEventHandler},
-        GlobalRule, Identifiable, Route, Service, Upstream, SSL,
+        Consumer, ConsumerGroup, GlobalRule, Identifiable, PluginConfig, Route, Service,
+        StreamRoute, Upstream, SSL,
     },
     core::status,
 };
 
 use super::{
+    consumer::{reload_consumer_credentials, ProxyConsumer, CONSUMER_MAP},
+    consumer_group::{ProxyConsumerGroup, CONSUMER_GROUP_MAP},
     global_rule::{reload_global_plugin, ProxyGlobalRule, GLOBAL_RULE_MAP},
-    route::{reload_global_route_match, ProxyRoute, ROUTE_MAP},
+    plugin_config::{ProxyPluginConfig, PLUGIN_CONFIG_MAP},
+    route::{
+        reload_global_route_match, schedule_route_match_update, schedule_service_routes_update,
+        ProxyRoute, ROUTE_MAP,
+    },
     service::{ProxyService, SERVICE_MAP},
     ssl::{reload_global_ssl_match, ProxySSL, SSL_MAP},
+    stream_route::{reload_global_stream_route_match, ProxyStreamRoute, STREAM_ROUTE_MAP},
     upstream::{ProxyUpstream, UPSTREAM_MAP},
     MapOperations,
 };
@@ -24,6 +32,10 @@ fn create_proxy_route(route: Route) -> pingora_error::Result<ProxyRoute> {
     ProxyRoute::new_with_upstream_and_plugins(route).map_err(|e| e.into())
 }
 
+fn create_proxy_stream_route(route: StreamRoute) -> pingora_error::Result<ProxyStreamRoute> {
+    ProxyStreamRoute::new_with_upstream(route).map_err(|e| e.into())
+}
+
 fn create_proxy_upstream(upstream: Upstream) -> pingora_error::Result<ProxyUpstream> {
     ProxyUpstream::new_with_shared_health_check(upstream).map_err(|e| e.into())
 }
@@ -36,11 +48,31 @@ fn create_proxy_global_rule(rule: GlobalRule) -> pingora_error::Result<ProxyGlob
     ProxyGlobalRule::new_with_plugins(rule).map_err(|e| e.into())
 }
 
+fn create_proxy_plugin_config(
+    plugin_config: PluginConfig,
+) -> pingora_error::Result<ProxyPluginConfig> {
+    ProxyPluginConfig::new_with_plugins(plugin_config).map_err(|e| e.into())
+}
+
+fn create_proxy_consumer(consumer: Consumer) -> pingora_error::Result<ProxyConsumer> {
+    ProxyConsumer::new(consumer).map_err(|e| e.into())
+}
+
+fn create_proxy_consumer_group(
+    consumer_group: ConsumerGroup,
+) -> pingora_error::Result<ProxyConsumerGroup> {
+    ProxyConsumerGroup::new_with_plugins(consumer_group).map_err(|e| e.into())
+}
+
 // Note: The following types must implement `Identifiable` in `crate::config`:
 // - Route
+// - StreamRoute
 // - Upstream
 // - Service
 // - GlobalRule
+// - PluginConfig
+// - Consumer
+// - ConsumerGroup
 // - SSL
 // Example implementation (add to `src/config/mod.rs` or relevant module):
 /*
@@ -66,6 +98,12 @@ impl InnerComparable<Route> for ProxyRoute {
     }
 }
 
+impl InnerComparable<StreamRoute> for ProxyStreamRoute {
+    fn inner_equals(&self, other: &StreamRoute) -> bool {
+        self.inner == *other
+    }
+}
+
 impl InnerComparable<Upstream> for ProxyUpstream {
     fn inner_equals(&self, other: &Upstream) -> bool {
         self.inner == *other
@@ -84,6 +122,24 @@ impl InnerComparable<GlobalRule> for ProxyGlobalRule {
     }
 }
 
+impl InnerComparable<PluginConfig> for ProxyPluginConfig {
+    fn inner_equals(&self, other: &PluginConfig) -> bool {
+        self.inner == *other
+    }
+}
+
+impl InnerComparable<Consumer> for ProxyConsumer {
+    fn inner_equals(&self, other: &Consumer) -> bool {
+        *self.inner == *other
+    }
+}
+
+impl InnerComparable<ConsumerGroup> for ProxyConsumerGroup {
+    fn inner_equals(&self, other: &ConsumerGroup) -> bool {
+        self.inner == *other
+    }
+}
+
 impl InnerComparable<SSL> for ProxySSL {
     fn inner_equals(&self, other: &SSL) -> bool {
         self.inner == *other
@@ -176,6 +232,16 @@ impl ProxyEventHandler {
         );
     }
 
+    fn handle_stream_routes(&self, response: &GetResponse) {
+        self.handle_list_resource(
+            response,
+            "stream_routes",
+            &*STREAM_ROUTE_MAP,
+            create_proxy_stream_route,
+            Some(reload_global_stream_route_match),
+        );
+    }
+
     fn handle_upstreams(&self, response: &GetResponse) {
         self.handle_list_resource(
             response,
@@ -196,6 +262,36 @@ impl ProxyEventHandler {
         );
     }
 
+    fn handle_plugin_configs(&self, response: &GetResponse) {
+        self.handle_list_resource(
+            response,
+            "plugin_configs",
+            &*PLUGIN_CONFIG_MAP,
+            create_proxy_plugin_config,
+            None,
+        );
+    }
+
+    fn handle_consumers(&self, response: &GetResponse) {
+        self.handle_list_resource(
+            response,
+            "consumers",
+            &*CONSUMER_MAP,
+            create_proxy_consumer,
+            Some(reload_consumer_credentials),
+        );
+    }
+
+    fn handle_consumer_groups(&self, response: &GetResponse) {
+        self.handle_list_resource(
+            response,
+            "consumer_groups",
+            &*CONSUMER_GROUP_MAP,
+            create_proxy_consumer_group,
+            None,
+        );
+    }
+
     fn handle_global_rules(&self, response: &GetResponse) {
         self.handle_list_resource(
             response,
@@ -266,7 +362,19 @@ impl ProxyEventHandler {
 
     fn handle_route_event(&self, event: &Event) {
         self.handle_resource(event, "routes", &*ROUTE_MAP, create_proxy_route);
-        reload_global_route_match();
+        if let Some(id) = event_resource_id(event) {
+            schedule_route_match_update([id]);
+        }
+    }
+
+    fn handle_stream_route_event(&self, event: &Event) {
+        self.handle_resource(
+            event,
+            "stream_routes",
+            &*STREAM_ROUTE_MAP,
+            create_proxy_stream_route,
+        );
+        reload_global_stream_route_match();
     }
 
     fn handle_upstream_event(&self, event: &Event) {
@@ -275,6 +383,10 @@ impl ProxyEventHandler {
 
     fn handle_service_event(&self, event: &Event) {
         self.handle_resource(event, "services", &*SERVICE_MAP, create_proxy_service);
+        // Service status and hosts affect how its routes are matched
+        if let Some(id) = event_resource_id(event) {
+            schedule_service_routes_update(&id);
+        }
     }
 
     fn handle_global_rule_event(&self, event: &Event) {
@@ -287,6 +399,29 @@ impl ProxyEventHandler {
         reload_global_plugin();
     }
 
+    fn handle_plugin_config_event(&self, event: &Event) {
+        self.handle_resource(
+            event,
+            "plugin_configs",
+            &*PLUGIN_CONFIG_MAP,
+            create_proxy_plugin_config,
+        );
+    }
+
+    fn handle_consumer_event(&self, event: &Event) {
+        self.handle_resource(event, "consumers", &*CONSUMER_MAP, create_proxy_consumer);
+        reload_consumer_credentials();
+    }
+
+    fn handle_consumer_group_event(&self, event: &Event) {
+        self.handle_resource(
+            event,
+            "consumer_groups",
+            &*CONSUMER_GROUP_MAP,
+            create_proxy_consumer_group,
+        );
+    }
+
     fn handle_ssl_event(&self, event: &Event) {
         self.handle_resource(event, "ssls", &*SSL_MAP, |ssl| Ok(ProxySSL::from(ssl)));
         reload_global_ssl_match();
@@ -314,9 +449,13 @@ impl EtcdEventHandler for ProxyEventHandler {
                     log::info!("Processing PUT event for key: {key}");
                     match key_type.as_str() {
                         "routes" => self.handle_route_event(event),
+                        "stream_routes" => self.handle_stream_route_event(event),
                         "upstreams" => self.handle_upstream_event(event),
                         "services" => self.handle_service_event(event),
                         "global_rules" => self.handle_global_rule_event(event),
+                        "plugin_configs" => self.handle_plugin_config_event(event),
+                        "consumers" => self.handle_consumer_event(event),
+                        "consumer_groups" => self.handle_consumer_group_event(event),
                         "ssls" => self.handle_ssl_event(event),
                         _ => log::warn!("Unhandled PUT event for key type: {key_type}"),
                     }
@@ -329,18 +468,33 @@ impl EtcdEventHandler for ProxyEventHandler {
                     match key_type.as_str() {
                         "routes" => {
                             ROUTE_MAP.remove(&id);
-                            reload_global_route_match();
+                            schedule_route_match_update([id]);
+                        }
+                        "stream_routes" => {
+                            STREAM_ROUTE_MAP.remove(&id);
+                            reload_global_stream_route_match();
                         }
                         "upstreams" => {
                             UPSTREAM_MAP.remove(&id);
                         }
                         "services" => {
                             SERVICE_MAP.remove(&id);
+                            schedule_service_routes_update(&id);
                         }
                         "global_rules" => {
                             GLOBAL_RULE_MAP.remove(&id);
                             reload_global_plugin();
                         }
+                        "plugin_configs" => {
+                            PLUGIN_CONFIG_MAP.remove(&id);
+                        }
+                        "consumers" => {
+                            CONSUMER_MAP.remove(&id);
+                            reload_consumer_credentials();
+                        }
+                        "consumer_groups" => {
+                            CONSUMER_GROUP_MAP.remove(&id);
+                        }
                         "ssls" => {
                             SSL_MAP.remove(&id);
                             reload_global_ssl_match();
@@ -358,13 +512,25 @@ impl EtcdEventHandler for ProxyEventHandler {
         self.handle_upstreams(response);
         self.handle_services(response);
         self.handle_global_rules(response);
+        self.handle_plugin_configs(response);
+        self.handle_consumer_groups(response);
+        self.handle_consumers(response);
         self.handle_routes(response);
+        self.handle_stream_routes(response);
 
         // Mark service as ready after successfully loading all configurations from etcd
         status::mark_ready(status::ConfigSource::Etcd);
     }
 }
 
+/// Returns the resource id from the key of an etcd event.
+fn event_resource_id(event: &Event) -> Option<String> {
+    event
+        .kv()
+        .and_then(|kv| parse_key(kv.key()).ok())
+        .map(|(id, _)| id)
+}
+
 /// Parses etcd key in the format `/prefix/resource_type/id`.
 fn parse_key(key: &[u8]) -> Result<(String, String), Box<dyn std::error::Error>> {
     let key = String::from_utf8(key.to_vec())?;
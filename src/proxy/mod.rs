@@ -3,11 +3,15 @@
 //! This module defines resource management and the generic
 //! `MapOperations` trait for managing resources in a thread-safe map.
 
+pub mod consumer;
+pub mod consumer_group;
 pub mod event;
 pub mod global_rule;
+pub mod plugin_config;
 pub mod route;
 pub mod service;
 pub mod ssl;
+pub mod stream_route;
 pub mod upstream;
 
 use std::{collections::HashSet, sync::Arc};
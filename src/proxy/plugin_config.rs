@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+use crate::{
+    config::{self, Identifiable},
+    core::{sort_plugins_by_priority_desc, ProxyError, ProxyPlugin, ProxyResult},
+    plugins::build_plugin,
+};
+
+use super::MapOperations;
+
+/// Fetches a plugin config by its ID.
+pub fn plugin_config_fetch(id: &str) -> Option<Arc<ProxyPluginConfig>> {
+    match PLUGIN_CONFIG_MAP.get(id) {
+        Some(plugin_config) => Some(plugin_config.value().clone()),
+        None => {
+            log::debug!("Plugin config '{id}' not found in cache");
+            None
+        }
+    }
+}
+
+/// Represents a reusable plugin bundle referenced by routes via `plugin_config_id`.
+pub struct ProxyPluginConfig {
+    pub inner: config::PluginConfig,
+    pub plugins: Vec<Arc<dyn ProxyPlugin>>,
+}
+
+impl Identifiable for ProxyPluginConfig {
+    fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.inner.id = id;
+    }
+}
+
+impl ProxyPluginConfig {
+    pub fn new_with_plugins(plugin_config: config::PluginConfig) -> ProxyResult<Self> {
+        let mut proxy_plugin_config = ProxyPluginConfig {
+            inner: plugin_config.clone(),
+            plugins: Vec::with_capacity(plugin_config.plugins.len()),
+        };
+
+        for (name, value) in plugin_config.plugins {
+            let plugin = build_plugin(&name, value).map_err(|e| {
+                ProxyError::Plugin(format!(
+                    "Failed to build plugin '{}' for plugin config '{}': {}",
+                    name, plugin_config.id, e
+                ))
+            })?;
+            proxy_plugin_config.plugins.push(plugin);
+        }
+
+        // Pre-sort plugins once at build-time to avoid per-request sorting in route merges.
+        sort_plugins_by_priority_desc(proxy_plugin_config.plugins.as_mut_slice());
+
+        Ok(proxy_plugin_config)
+    }
+}
+
+/// Global map to store plugin configs, initialized lazily.
+pub static PLUGIN_CONFIG_MAP: Lazy<DashMap<String, Arc<ProxyPluginConfig>>> =
+    Lazy::new(DashMap::new);
+
+/// Loads plugin configs from the given configuration.
+pub fn load_static_plugin_configs(config: &config::Config) -> ProxyResult<()> {
+    let proxy_plugin_configs: Vec<Arc<ProxyPluginConfig>> = config
+        .plugin_configs
+        .iter()
+        .map(|plugin_config| {
+            log::info!("Configuring PluginConfig: {}", plugin_config.id);
+            match ProxyPluginConfig::new_with_plugins(plugin_config.clone()) {
+                Ok(proxy_plugin_config) => Ok(Arc::new(proxy_plugin_config)),
+                Err(e) => {
+                    log::error!(
+                        "Failed to configure PluginConfig {}: {}",
+                        plugin_config.id,
+                        e
+                    );
+                    Err(e)
+                }
+            }
+        })
+        .collect::<ProxyResult<Vec<_>>>()?;
+
+    PLUGIN_CONFIG_MAP.reload_resources(proxy_plugin_configs);
+
+    Ok(())
+}
@@ -1,13 +1,20 @@
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use std::time::Duration;
 
 use arc_swap::ArcSwap;
 use dashmap::DashMap;
+use ipnetwork::IpNetwork;
 use matchit::{InsertError, Router as MatchRouter};
 use once_cell::sync::Lazy;
 use pingora_core::upstreams::peer::HttpPeer;
 use pingora_error::Result;
+use pingora_load_balancing::Backend;
 use pingora_proxy::Session;
+use regex::{Regex, RegexBuilder};
 
 use crate::{
     config::{self, Identifiable},
@@ -16,10 +23,14 @@ use crate::{
         ProxyResult, RouteContext, UpstreamSelector,
     },
     plugins::build_plugin,
-    utils::request::get_request_host,
+    utils::{
+        request::{get_request_host, is_tls_session},
+        vars::VarsExpr,
+    },
 };
 
 use super::{
+    plugin_config::plugin_config_fetch,
     service::service_fetch,
     upstream::{upstream_fetch, ProxyUpstream},
     MapOperations,
@@ -41,6 +52,8 @@ fn route_overrides_plugin(route_plugin_names: &[String], plugin_name: &str) -> b
 
 /// Merge two already-sorted plugin lists (priority desc) into a single list.
 ///
+/// Also used to merge plugin config plugins, which take the "service" side.
+///
 /// - Maintains global priority ordering (descending).
 /// - When a plugin name exists in route, service plugin with same name is skipped.
 /// - On equal priority, prefers route plugins for deterministic results.
@@ -97,12 +110,19 @@ type RouteMatchResult = Option<(Vec<(String, String)>, Arc<ProxyRoute>)>;
 /// Proxy route with upstream and plugin configuration.
 ///
 /// Routes are compiled at startup and cached for high-performance request matching.
-/// Plugin executors are cached for routes without service_id (static plugins),
-/// and dynamically built for routes with service_id (to reflect service updates).
+/// Plugin executors are cached for routes without service_id or plugin_config_id
+/// (static plugins), and dynamically built otherwise (to reflect service and plugin
+/// config updates).
 pub struct ProxyRoute {
     pub inner: config::Route,
     pub upstream: Option<Arc<ProxyUpstream>>,
     pub plugins: Vec<Arc<dyn ProxyPlugin>>,
+    /// Parsed URI patterns, one per configured uri.
+    uri_patterns: Vec<UriPattern>,
+    /// Client networks allowed to match, from `remote_addr`/`remote_addrs`.
+    remote_addrs: Vec<IpNetwork>,
+    /// Compiled `vars` expressions evaluated during matching.
+    vars: VarsExpr,
     /// Sorted index of route plugin names for fast "route overrides service" checks.
     plugin_name_index: Vec<String>,
     /// Cached executor for the route (unified cache for static routes)
@@ -121,10 +141,21 @@ impl Identifiable for ProxyRoute {
 
 impl ProxyRoute {
     pub fn new_with_upstream_and_plugins(route: config::Route) -> ProxyResult<Self> {
+        let vars = VarsExpr::compile(&route.vars)
+            .with_context(&format!("Failed to compile vars for route '{}'", route.id))?;
+        let uri_patterns = route
+            .get_uris()
+            .into_iter()
+            .map(UriPattern::parse)
+            .collect::<ProxyResult<Vec<_>>>()?;
+
         let mut proxy_route = ProxyRoute {
             inner: route.clone(),
             upstream: None,
             plugins: Vec::with_capacity(route.plugins.len()),
+            uri_patterns,
+            remote_addrs: route.get_remote_addrs(),
+            vars,
             plugin_name_index: Vec::new(),
             cached_executor: None,
         };
@@ -149,9 +180,10 @@ impl ProxyRoute {
         sort_plugins_by_priority_desc(proxy_route.plugins.as_mut_slice());
         proxy_route.plugin_name_index = build_plugin_name_index(&proxy_route.plugins);
 
-        // Optimization: Pre-build executor for routes without service_id (static plugins).
+        // Optimization: Pre-build executor for routes without service_id or
+        // plugin_config_id (static plugins).
         // Use shared empty executor to avoid allocations when no plugins are configured.
-        if route.service_id.is_none() {
+        if route.service_id.is_none() && route.plugin_config_id.is_none() {
             let executor = if proxy_route.plugins.is_empty() {
                 ProxyPluginExecutor::default_shared()
             } else {
@@ -177,6 +209,92 @@ impl ProxyRoute {
         vec![]
     }
 
+    /// Checks the non-URI predicates (methods, vars) against the request.
+    fn accepts(&self, method: &str, session: &Session) -> bool {
+        if !self.inner.methods.is_empty() && !self.inner.methods.iter().any(|m| *m == method) {
+            return false;
+        }
+
+        let client_ip = session
+            .client_addr()
+            .and_then(|addr| addr.as_inet())
+            .map(|inet| inet.ip());
+        let server_addr = session
+            .server_addr()
+            .and_then(|addr| addr.as_inet())
+            .copied();
+        if !self.accepts_connection(client_ip, server_addr, is_tls_session(session)) {
+            return false;
+        }
+
+        self.vars.is_empty() || self.vars.matches(session)
+    }
+
+    /// Checks client address, listener and scheme restrictions of the route.
+    fn accepts_connection(
+        &self,
+        client_ip: Option<IpAddr>,
+        server_addr: Option<SocketAddr>,
+        tls: bool,
+    ) -> bool {
+        if !self.remote_addrs.is_empty()
+            && !client_ip.is_some_and(|ip| self.remote_addrs.iter().any(|n| n.contains(ip)))
+        {
+            return false;
+        }
+
+        if !self.inner.listeners.is_empty()
+            && !server_addr.is_some_and(|addr| {
+                self.inner.listeners.iter().any(|l| {
+                    l.port() == addr.port() && (l.ip().is_unspecified() || l.ip() == addr.ip())
+                })
+            })
+        {
+            return false;
+        }
+
+        match self.inner.scheme {
+            Some(config::RouteScheme::HTTP) => !tls,
+            Some(config::RouteScheme::HTTPS) => tls,
+            None => true,
+        }
+    }
+
+    /// Returns false if the route or its bound service is disabled via `status`.
+    fn is_enabled(&self) -> bool {
+        self.inner.status.is_enabled()
+            && self
+                .inner
+                .service_id
+                .as_deref()
+                .and_then(service_fetch)
+                .is_none_or(|service| service.inner.status.is_enabled())
+    }
+
+    /// Host keys the route is matched under: route hosts, falling back to service
+    /// hosts, or the host-less bucket when neither is configured.
+    fn host_keys(&self) -> Vec<HostKey> {
+        let route_hosts = self.get_hosts();
+        let mut keys: Vec<HostKey> = if !route_hosts.is_empty() {
+            route_hosts
+                .iter()
+                .map(|host| Some(MatchEntry::reverse_host(host)))
+                .collect()
+        } else {
+            self.get_service_hosts()
+                .iter()
+                .map(|host| Some(MatchEntry::reverse_host(host)))
+                .collect()
+        };
+
+        if keys.is_empty() {
+            keys.push(None);
+        }
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
     /// Gets service hosts when route hosts are empty (requires owned strings).
     fn get_service_hosts(&self) -> Vec<String> {
         if let Some(service) = self
@@ -201,14 +319,18 @@ impl RouteContext for ProxyRoute {
         self.inner.service_id.as_deref()
     }
 
-    fn select_http_peer(&self, session: &mut Session) -> ProxyResult<Box<HttpPeer>> {
+    fn select_backend(
+        &self,
+        session: &mut Session,
+        excluded: &[pingora_core::protocols::l4::socket::SocketAddr],
+    ) -> ProxyResult<Backend> {
         let upstream = self.resolve_upstream().ok_or_else(|| {
             ProxyError::UpstreamSelection(
                 "Failed to retrieve upstream configuration for route".to_string(),
             )
         })?;
 
-        let mut backend = upstream.select_backend(session).ok_or_else(|| {
+        let mut backend = upstream.select_backend(session, excluded).ok_or_else(|| {
             ProxyError::UpstreamSelection(format!(
                 "No healthy backend available for route '{}'",
                 self.inner.id
@@ -222,7 +344,7 @@ impl RouteContext for ProxyRoute {
         })?;
 
         self.set_timeout(peer);
-        Ok(Box::new(peer.clone()))
+        Ok(backend)
     }
 
     fn build_plugin_executor(&self) -> Arc<ProxyPluginExecutor> {
@@ -231,38 +353,40 @@ impl RouteContext for ProxyRoute {
             return executor.clone();
         }
 
-        // Fetch current service (if configured)
+        // Slow path: fetch the current plugin config and service (if configured)
+        let plugin_config = self
+            .inner
+            .plugin_config_id
+            .as_deref()
+            .and_then(plugin_config_fetch);
         let service = self.inner.service_id.as_deref().and_then(service_fetch);
 
-        // Slow path: build executor and update cache
-        let Some(current_service) = service else {
-            // No service found (might be deleted or misconfigured)
-            // Fallback to route-only plugins
-            let executor = if self.plugins.is_empty() {
-                ProxyPluginExecutor::default_shared()
-            } else {
-                Arc::new(ProxyPluginExecutor {
-                    plugins: self.plugins.clone(),
-                })
-            };
-            return executor;
+        // Precedence on name conflicts: route > plugin config > service
+        let (mut plugins, plugin_names) = match plugin_config {
+            Some(plugin_config) => {
+                let merged = merge_route_and_service_plugins(
+                    &self.plugins,
+                    &plugin_config.plugins,
+                    &self.plugin_name_index,
+                );
+                let names = build_plugin_name_index(&merged);
+                (merged, Cow::Owned(names))
+            }
+            None => (
+                self.plugins.clone(),
+                Cow::Borrowed(self.plugin_name_index.as_slice()),
+            ),
         };
 
-        // Dynamically merge route and service plugins
-        let service_plugins: &[Arc<dyn ProxyPlugin>] = current_service.plugins.as_slice();
-
-        let merged_plugins = merge_route_and_service_plugins(
-            &self.plugins,
-            service_plugins,
-            &self.plugin_name_index,
-        );
+        // A missing service (deleted or misconfigured) falls back to route-level plugins
+        if let Some(service) = service {
+            plugins = merge_route_and_service_plugins(&plugins, &service.plugins, &plugin_names);
+        }
 
-        if merged_plugins.is_empty() {
+        if plugins.is_empty() {
             ProxyPluginExecutor::default_shared()
         } else {
-            Arc::new(ProxyPluginExecutor {
-                plugins: merged_plugins,
-            })
+            Arc::new(ProxyPluginExecutor { plugins })
         }
     }
 
@@ -284,6 +408,19 @@ impl RouteContext for ProxyRoute {
                     .and_then(|id| service_fetch(id).and_then(|s| s.resolve_upstream()))
             })
     }
+
+    fn websocket_timeout(&self) -> Option<config::WebSocketTimeout> {
+        match self.inner.enable_websocket {
+            Some(true) => Some(self.inner.websocket_timeout.unwrap_or_default()),
+            Some(false) => None,
+            // Upgrades pass through, with the route timeouts unless configured
+            None => self.inner.websocket_timeout,
+        }
+    }
+
+    fn websocket_disabled(&self) -> bool {
+        self.inner.enable_websocket == Some(false)
+    }
 }
 
 impl ProxyRoute {
@@ -302,107 +439,196 @@ impl ProxyRoute {
     }
 }
 
-#[derive(Default)]
-pub struct MatchEntry {
-    /// Router for non-host URI matching
-    non_host_uri: MatchRouter<Vec<Arc<ProxyRoute>>>,
-    /// Router for host URI matching
-    host_uris: MatchRouter<MatchRouter<Vec<Arc<ProxyRoute>>>>,
+/// A route URI pattern, classified by its syntax.
+enum UriPattern {
+    /// Handled by matchit: exact paths, `{param}` and `{*catchall}`.
+    Router(String),
+    /// `^~/prefix`: nginx-style prefix match where the longest prefix wins.
+    Prefix(String),
+    /// `~regex` or `~*regex` (case-insensitive); capture groups become route params.
+    Regex(Regex),
 }
 
-impl MatchEntry {
-    /// Converts host to reversed matchit-compatible pattern.
-    /// Wildcard "*.example.com" becomes "moc.elpmaxe.{*subdomain}".
-    /// Exact "api.example.com" becomes "moc.elpmaxe.ipa".
-    fn reverse_host(host: &str) -> String {
-        let host = host.to_ascii_lowercase();
-        if let Some(domain_part) = host.strip_prefix("*") {
-            let reversed_domain: String = domain_part.chars().rev().collect();
-            format!("{reversed_domain}{{*subdomain}}")
+impl UriPattern {
+    fn parse(uri: &str) -> ProxyResult<Self> {
+        if let Some(prefix) = uri.strip_prefix("^~") {
+            return Ok(UriPattern::Prefix(prefix.trim_start().to_string()));
+        }
+
+        let (pattern, case_insensitive) = if let Some(p) = uri.strip_prefix("~*") {
+            (p, true)
+        } else if let Some(p) = uri.strip_prefix('~') {
+            (p, false)
         } else {
-            host.chars().rev().collect()
+            return Ok(UriPattern::Router(uri.to_string()));
+        };
+
+        RegexBuilder::new(pattern.trim_start())
+            .case_insensitive(case_insensitive)
+            .build()
+            .map(UriPattern::Regex)
+            .map_err(|e| ProxyError::Configuration(format!("Invalid regex uri '{uri}': {e}")))
+    }
+}
+
+/// URI matcher for a single host, or for routes without hosts.
+///
+/// Lookup order is:
+/// 1. the matchit tier (exact paths, `{param}`, `{*catchall}`),
+/// 2. the longest matching `^~` prefix,
+/// 3. `~`/`~*` regexes in route priority order.
+///
+/// Candidates rejected by the request predicate (methods, vars) fall through
+/// to the next candidate and then to the next tier.
+#[derive(Default)]
+struct UriRouter {
+    router: MatchRouter<Vec<Arc<ProxyRoute>>>,
+    /// Sorted by prefix length desc, then route priority desc.
+    prefixes: Vec<(String, Arc<ProxyRoute>)>,
+    /// Sorted by route priority desc, then route id for determinism.
+    regexes: Vec<(Regex, Arc<ProxyRoute>)>,
+}
+
+impl UriRouter {
+    /// Builds a router from `routes`, skipping routes whose uris conflict.
+    fn build<'a>(routes: impl Iterator<Item = &'a Arc<ProxyRoute>>) -> Self {
+        // Insert in id order so equal-priority ties resolve deterministically.
+        let mut routes: Vec<&Arc<ProxyRoute>> = routes.collect();
+        routes.sort_by(|a, b| a.inner.id.cmp(&b.inner.id));
+
+        let mut router = Self::default();
+        for route in routes {
+            log::debug!("Inserting route: {}", route.inner.id);
+            for pattern in &route.uri_patterns {
+                if let Err(e) = router.insert(pattern, route.clone()) {
+                    log::error!("Failed to insert route {}: {}", route.inner.id, e);
+                }
+            }
         }
+        router.sort();
+        router
     }
 
-    fn insert_into_router(
-        router: &mut MatchRouter<Vec<Arc<ProxyRoute>>>,
-        uri: &str,
+    fn insert(
+        &mut self,
+        pattern: &UriPattern,
         proxy_route: Arc<ProxyRoute>,
     ) -> Result<(), InsertError> {
-        match router.at_mut(uri) {
-            Ok(routes) => {
-                routes.value.push(proxy_route);
-                // Sort routes by priority (higher priority values take precedence)
-                routes
-                    .value
-                    .sort_by_key(|b| std::cmp::Reverse(b.inner.priority));
+        match pattern {
+            UriPattern::Router(uri) => match self.router.at_mut(uri) {
+                Ok(routes) => {
+                    // Keep routes by priority (higher priority values take precedence), after
+                    // the routes of the same priority
+                    let index = routes
+                        .value
+                        .partition_point(|r| r.inner.priority >= proxy_route.inner.priority);
+                    routes.value.insert(index, proxy_route);
+                }
+                Err(_) => {
+                    self.router.insert(uri, vec![proxy_route])?;
+                }
+            },
+            UriPattern::Prefix(prefix) => {
+                self.prefixes.push((prefix.clone(), proxy_route));
             }
-            Err(_) => {
-                router.insert(uri, vec![proxy_route])?;
+            UriPattern::Regex(re) => {
+                self.regexes.push((re.clone(), proxy_route));
             }
         }
         Ok(())
     }
 
-    /// Inserts a route into the match entry.
-    pub fn insert_route(&mut self, proxy_route: Arc<ProxyRoute>) -> Result<(), InsertError> {
-        let route_hosts = proxy_route.get_hosts();
-        let uris = proxy_route.inner.get_uris();
+    /// Orders the prefix and regex tiers for matching, once all routes are inserted.
+    fn sort(&mut self) {
+        self.prefixes.sort_by(|(pa, ra), (pb, rb)| {
+            pb.len()
+                .cmp(&pa.len())
+                .then_with(|| rb.inner.priority.cmp(&ra.inner.priority))
+        });
+        self.regexes.sort_by(|(_, ra), (_, rb)| {
+            rb.inner
+                .priority
+                .cmp(&ra.inner.priority)
+                .then_with(|| ra.inner.id.cmp(&rb.inner.id))
+        });
+    }
 
-        // Try route hosts first, fall back to service hosts
-        let has_hosts = if !route_hosts.is_empty() {
-            self.insert_host_uris(route_hosts.iter().copied(), &uris, &proxy_route)?;
-            true
-        } else {
-            let service_hosts = proxy_route.get_service_hosts();
-            if !service_hosts.is_empty() {
-                self.insert_host_uris(
-                    service_hosts.iter().map(|s| s.as_str()),
-                    &uris,
-                    &proxy_route,
-                )?;
-                true
-            } else {
-                false
+    fn match_uri<F>(&self, uri: &str, accept: &F) -> RouteMatchResult
+    where
+        F: Fn(&ProxyRoute) -> bool,
+    {
+        if let Ok(v) = self.router.at(uri) {
+            if let Some(route) = v.value.iter().find(|r| accept(r)) {
+                // Convert params to Vec - more efficient for small number of params (typical case)
+                let params: Vec<(String, String)> = v
+                    .params
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect();
+                return Some((params, route.clone()));
             }
-        };
+        }
+
+        if let Some((_, route)) = self
+            .prefixes
+            .iter()
+            .find(|(prefix, route)| uri.starts_with(prefix.as_str()) && accept(route))
+        {
+            return Some((Vec::new(), route.clone()));
+        }
 
-        if !has_hosts {
-            for uri in &uris {
-                Self::insert_into_router(&mut self.non_host_uri, uri, proxy_route.clone())?;
+        for (re, route) in &self.regexes {
+            let Some(caps) = re.captures(uri) else {
+                continue;
+            };
+            if !accept(route) {
+                continue;
             }
+
+            // Named groups are keyed by name, unnamed groups by their index.
+            let params = re
+                .capture_names()
+                .enumerate()
+                .skip(1)
+                .filter_map(|(i, name)| {
+                    caps.get(i).map(|m| {
+                        let key = name.map_or_else(|| i.to_string(), |n| n.to_string());
+                        (key, m.as_str().to_string())
+                    })
+                })
+                .collect();
+            return Some((params, route.clone()));
         }
 
-        Ok(())
+        None
     }
+}
 
-    fn insert_host_uris<'a>(
-        &mut self,
-        hosts: impl Iterator<Item = &'a str>,
-        uris: &[&str],
-        proxy_route: &Arc<ProxyRoute>,
-    ) -> Result<(), InsertError> {
-        for host in hosts {
-            let processed_host = Self::reverse_host(host);
-            let inner_router = self.host_uris.at_mut(processed_host.as_str());
+/// Host key a route is indexed under: the reversed host pattern (see
+/// [`MatchEntry::reverse_host`]), or `None` for routes without hosts.
+type HostKey = Option<String>;
 
-            let inner_router = match inner_router {
-                Ok(router) => router.value,
-                Err(_) => {
-                    let new_router = MatchRouter::new();
-                    self.host_uris.insert(processed_host.clone(), new_router)?;
-                    self.host_uris
-                        .at_mut(processed_host.as_str())
-                        .unwrap()
-                        .value
-                }
-            };
+/// Immutable route matcher snapshot published through `GLOBAL_ROUTE_MATCH`.
+#[derive(Default)]
+pub struct MatchEntry {
+    /// Router for non-host URI matching
+    non_host_uri: Arc<UriRouter>,
+    /// Router for host URI matching
+    host_uris: MatchRouter<Arc<UriRouter>>,
+}
 
-            for uri in uris {
-                Self::insert_into_router(inner_router, uri, proxy_route.clone())?;
-            }
+impl MatchEntry {
+    /// Converts host to reversed matchit-compatible pattern.
+    /// Wildcard "*.example.com" becomes "moc.elpmaxe.{*subdomain}".
+    /// Exact "api.example.com" becomes "moc.elpmaxe.ipa".
+    fn reverse_host(host: &str) -> String {
+        let host = host.to_ascii_lowercase();
+        if let Some(domain_part) = host.strip_prefix("*") {
+            let reversed_domain: String = domain_part.chars().rev().collect();
+            format!("{reversed_domain}{{*subdomain}}")
+        } else {
+            host.chars().rev().collect()
         }
-        Ok(())
     }
 
     /// Matches a request to a route.
@@ -413,6 +639,15 @@ impl MatchEntry {
 
         log::debug!("match request: host={host:?}, uri={uri:?}, method={method:?}");
 
+        let session = &*session;
+        self.match_host_uri(host, uri, |route| route.accepts(method, session))
+    }
+
+    /// Matches host and URI, using `accept` to check each candidate route.
+    fn match_host_uri<F>(&self, host: Option<&str>, uri: &str, accept: F) -> RouteMatchResult
+    where
+        F: Fn(&ProxyRoute) -> bool,
+    {
         // Attempt to match using host_uris if a valid host is provided
         if let Some(host_str) = host.filter(|h| !h.is_empty()) {
             // Just reverse the host and let matchit handle the matching
@@ -420,42 +655,95 @@ impl MatchEntry {
             let normalized_host = host_str.to_ascii_lowercase();
             let reversed_host = normalized_host.chars().rev().collect::<String>();
             if let Ok(v) = self.host_uris.at(&reversed_host) {
-                if let Some(result) = Self::match_uri_method(v.value, uri, method) {
+                if let Some(result) = v.value.match_uri(uri, &accept) {
                     return Some(result);
                 }
             }
         }
 
         // Fall back to non-host URI matching
-        Self::match_uri_method(&self.non_host_uri, uri, method)
-    }
-
-    /// Matches a URI to a route.
-    fn match_uri_method(
-        match_router: &MatchRouter<Vec<Arc<ProxyRoute>>>,
-        uri: &str,
-        method: &str,
-    ) -> RouteMatchResult {
-        if let Ok(v) = match_router.at(uri) {
-            // Convert params to Vec - more efficient for small number of params (typical case)
-            let params: Vec<(String, String)> = v
-                .params
-                .iter()
-                .map(|(k, v)| (k.to_string(), v.to_string()))
-                .collect();
+        self.non_host_uri.match_uri(uri, &accept)
+    }
+}
 
-            for route in v.value.iter() {
-                if route.inner.methods.is_empty() {
-                    return Some((params, route.clone()));
+/// Incrementally maintained index from which [`MatchEntry`] snapshots are built.
+///
+/// Every host (and the host-less bucket) owns a prebuilt [`UriRouter`]. Applying a
+/// route change only rebuilds the routers of the hosts the route was or is bound to;
+/// untouched routers are shared between snapshots.
+#[derive(Default)]
+struct RouteMatchIndex {
+    /// Host keys each route is currently registered under.
+    route_keys: HashMap<String, Vec<HostKey>>,
+    /// Routes registered under each host key, by route id.
+    key_routes: HashMap<HostKey, HashMap<String, Arc<ProxyRoute>>>,
+    /// Prebuilt URI routers per host key.
+    routers: HashMap<HostKey, Arc<UriRouter>>,
+}
+
+impl RouteMatchIndex {
+    /// Replaces route `id` with `route`, or removes it when `route` is `None` or disabled.
+    ///
+    /// Host keys whose routers need rebuilding are added to `dirty`.
+    fn apply(&mut self, id: &str, route: Option<Arc<ProxyRoute>>, dirty: &mut HashSet<HostKey>) {
+        for key in self.route_keys.remove(id).unwrap_or_default() {
+            if let Some(routes) = self.key_routes.get_mut(&key) {
+                routes.remove(id);
+                if routes.is_empty() {
+                    self.key_routes.remove(&key);
                 }
+            }
+            dirty.insert(key);
+        }
 
-                // Match method
-                if route.inner.methods.iter().any(|m| *m == method) {
-                    return Some((params, route.clone()));
+        let Some(route) = route else {
+            return;
+        };
+        if !route.is_enabled() {
+            log::debug!("Skipping disabled route: {id}");
+            return;
+        }
+
+        let keys = route.host_keys();
+        for key in &keys {
+            self.key_routes
+                .entry(key.clone())
+                .or_default()
+                .insert(id.to_string(), route.clone());
+            dirty.insert(key.clone());
+        }
+        self.route_keys.insert(id.to_string(), keys);
+    }
+
+    /// Rebuilds the routers of the given host keys.
+    fn rebuild(&mut self, dirty: HashSet<HostKey>) {
+        for key in dirty {
+            match self.key_routes.get(&key) {
+                Some(routes) => {
+                    self.routers
+                        .insert(key, Arc::new(UriRouter::build(routes.values())));
+                }
+                None => {
+                    self.routers.remove(&key);
                 }
             }
         }
-        None
+    }
+
+    /// Assembles a matcher snapshot from the prebuilt routers.
+    fn snapshot(&self) -> MatchEntry {
+        let mut entry = MatchEntry::default();
+        for (key, router) in &self.routers {
+            match key {
+                Some(host) => {
+                    if let Err(e) = entry.host_uris.insert(host.clone(), router.clone()) {
+                        log::error!("Failed to insert host '{host}': {e}");
+                    }
+                }
+                None => entry.non_host_uri = router.clone(),
+            }
+        }
+        entry
     }
 }
 
@@ -468,18 +756,99 @@ pub fn global_route_match_fetch() -> Arc<MatchEntry> {
     GLOBAL_ROUTE_MATCH.load().clone()
 }
 
+/// Rebuilds the route matcher from scratch using the current `ROUTE_MAP`.
+///
+/// Used for full (re)loads; single resource changes should go through
+/// [`schedule_route_match_update`] instead.
 pub fn reload_global_route_match() {
-    let mut matcher = MatchEntry::default();
+    let mut index = lock_or_recover(&ROUTE_MATCH_INDEX);
+    *index = RouteMatchIndex::default();
 
+    let mut dirty = HashSet::new();
     for route in ROUTE_MAP.iter() {
-        log::debug!("Inserting route: {}", route.inner.id);
-        if let Err(e) = matcher.insert_route(route.clone()) {
-            log::error!("Failed to insert route {}: {}", route.inner.id, e);
-            // Continue with other routes to avoid partial failures stopping the process
+        index.apply(route.key(), Some(route.value().clone()), &mut dirty);
+    }
+    index.rebuild(dirty);
+
+    GLOBAL_ROUTE_MATCH.store(Arc::new(index.snapshot()));
+}
+
+/// Delay used to coalesce bursts of route changes into a single matcher swap.
+const ROUTE_UPDATE_DEBOUNCE: Duration = Duration::from_millis(50);
+
+static ROUTE_MATCH_INDEX: Lazy<Mutex<RouteMatchIndex>> =
+    Lazy::new(|| Mutex::new(RouteMatchIndex::default()));
+static PENDING_ROUTE_UPDATES: Lazy<Mutex<HashSet<String>>> =
+    Lazy::new(|| Mutex::new(HashSet::new()));
+static ROUTE_UPDATE_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+fn lock_or_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Queues route ids whose `ROUTE_MAP` entry was inserted, replaced or removed.
+///
+/// Updates are debounced: all ids queued within [`ROUTE_UPDATE_DEBOUNCE`] are applied
+/// together and published with a single `GLOBAL_ROUTE_MATCH` swap. Outside of a
+/// tokio runtime the update is applied immediately.
+pub fn schedule_route_match_update<I>(route_ids: I)
+where
+    I: IntoIterator<Item = String>,
+{
+    lock_or_recover(&PENDING_ROUTE_UPDATES).extend(route_ids);
+
+    if ROUTE_UPDATE_SCHEDULED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(async {
+                tokio::time::sleep(ROUTE_UPDATE_DEBOUNCE).await;
+                flush_route_match_updates();
+            });
         }
+        Err(_) => flush_route_match_updates(),
     }
+}
+
+/// Queues every route bound to `service_id`, since service status and hosts
+/// affect how those routes are matched.
+pub fn schedule_service_routes_update(service_id: &str) {
+    let route_ids: Vec<String> = ROUTE_MAP
+        .iter()
+        .filter(|route| route.inner.service_id.as_deref() == Some(service_id))
+        .map(|route| route.key().clone())
+        .collect();
 
-    GLOBAL_ROUTE_MATCH.store(Arc::new(matcher));
+    if !route_ids.is_empty() {
+        schedule_route_match_update(route_ids);
+    }
+}
+
+fn flush_route_match_updates() {
+    // Clear the flag before draining so updates queued from now on schedule a new flush.
+    ROUTE_UPDATE_SCHEDULED.store(false, Ordering::Release);
+    let route_ids = std::mem::take(&mut *lock_or_recover(&PENDING_ROUTE_UPDATES));
+    if route_ids.is_empty() {
+        return;
+    }
+
+    let mut index = lock_or_recover(&ROUTE_MATCH_INDEX);
+    let mut dirty = HashSet::new();
+    for id in &route_ids {
+        let route = ROUTE_MAP.get(id).map(|route| route.value().clone());
+        index.apply(id, route, &mut dirty);
+    }
+
+    log::debug!(
+        "Applying {} route updates, rebuilding {} host routers",
+        route_ids.len(),
+        dirty.len()
+    );
+    index.rebuild(dirty);
+
+    GLOBAL_ROUTE_MATCH.store(Arc::new(index.snapshot()));
 }
 
 /// Loads routes from the given configuration.
@@ -509,6 +878,7 @@ pub fn load_static_routes(config: &config::Config) -> ProxyResult<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proxy::plugin_config::{ProxyPluginConfig, PLUGIN_CONFIG_MAP};
     use async_trait::async_trait;
     use serde_json::Value as JsonValue;
     use std::collections::HashMap;
@@ -681,15 +1051,326 @@ mod tests {
             host: None,
             hosts: vec![],
             priority: 0,
+            vars: vec![],
+            remote_addr: None,
+            remote_addrs: vec![],
+            listeners: vec![],
+            scheme: None,
+            status: config::ResourceStatus::Enabled,
             plugins: HashMap::<String, JsonValue>::new(),
             upstream: None,
             upstream_id: Some("u1".to_string()),
             service_id: None,
+            plugin_config_id: None,
             timeout: None,
+            enable_websocket: None,
+            websocket_timeout: None,
         };
 
         let proxy_route = ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap();
         let exec = proxy_route.build_plugin_executor();
         assert!(Arc::ptr_eq(&exec, &ProxyPluginExecutor::default_shared()));
     }
+
+    #[test]
+    fn test_route_websocket_default() {
+        // Routes that do not set the flag keep proxying upgrades with their own timeouts
+        let route = test_route("ws", &["/"], 0);
+        assert!(!route.websocket_disabled());
+        assert!(route.websocket_timeout().is_none());
+
+        let timeout = config::WebSocketTimeout { idle: 60, read: 60 };
+        let route_cfg = config::Route {
+            websocket_timeout: Some(timeout),
+            ..route.inner.clone()
+        };
+        let route = ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap();
+        assert!(!route.websocket_disabled());
+        assert_eq!(route.websocket_timeout(), Some(timeout));
+
+        let route_cfg = config::Route {
+            enable_websocket: Some(true),
+            websocket_timeout: None,
+            ..route.inner.clone()
+        };
+        let route = ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap();
+        assert_eq!(
+            route.websocket_timeout(),
+            Some(config::WebSocketTimeout::default())
+        );
+
+        let route_cfg = config::Route {
+            enable_websocket: Some(false),
+            ..route.inner.clone()
+        };
+        let route = ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap();
+        assert!(route.websocket_disabled());
+        assert!(route.websocket_timeout().is_none());
+    }
+
+    #[test]
+    fn test_plugin_config_merge_precedence() {
+        let config_echo: Arc<dyn ProxyPlugin> = Arc::new(DummyPlugin {
+            name: "echo",
+            priority: 100,
+        });
+        let config_only: Arc<dyn ProxyPlugin> = Arc::new(DummyPlugin {
+            name: "config_only",
+            priority: 5,
+        });
+        PLUGIN_CONFIG_MAP.insert(
+            "test-merge-pc".to_string(),
+            Arc::new(ProxyPluginConfig {
+                inner: config::PluginConfig {
+                    id: "test-merge-pc".to_string(),
+                    plugins: HashMap::new(),
+                },
+                plugins: vec![config_echo.clone(), config_only.clone()],
+            }),
+        );
+
+        let route_cfg = config::Route {
+            plugins: HashMap::from([("echo".to_string(), serde_json::json!({"body": "route"}))]),
+            plugin_config_id: Some("test-merge-pc".to_string()),
+            ..test_route("pc", &["/"], 0).inner.clone()
+        };
+        let route = ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap();
+        assert!(route.cached_executor.is_none());
+
+        let exec = route.build_plugin_executor();
+        assert_eq!(exec.plugins.len(), 2);
+        // Route plugin overrides the bundle plugin with the same name
+        assert!(Arc::ptr_eq(&exec.plugins[0], &route.plugins[0]));
+        assert!(Arc::ptr_eq(&exec.plugins[1], &config_only));
+
+        PLUGIN_CONFIG_MAP.remove("test-merge-pc");
+        let exec = route.build_plugin_executor();
+        assert_eq!(exec.plugins.len(), 1);
+    }
+
+    fn test_route(id: &str, uris: &[&str], priority: u32) -> Arc<ProxyRoute> {
+        let route_cfg = config::Route {
+            id: id.to_string(),
+            uri: None,
+            uris: uris.iter().map(|u| u.to_string()).collect(),
+            methods: vec![],
+            host: None,
+            hosts: vec![],
+            priority,
+            vars: vec![],
+            remote_addr: None,
+            remote_addrs: vec![],
+            listeners: vec![],
+            scheme: None,
+            status: config::ResourceStatus::Enabled,
+            plugins: HashMap::<String, JsonValue>::new(),
+            upstream: None,
+            upstream_id: Some("u1".to_string()),
+            service_id: None,
+            plugin_config_id: None,
+            timeout: None,
+            enable_websocket: None,
+            websocket_timeout: None,
+        };
+        Arc::new(ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap())
+    }
+
+    fn build_entry(routes: impl IntoIterator<Item = Arc<ProxyRoute>>) -> MatchEntry {
+        let mut index = RouteMatchIndex::default();
+        let mut dirty = HashSet::new();
+        for route in routes {
+            let id = route.inner.id.clone();
+            index.apply(&id, Some(route), &mut dirty);
+        }
+        index.rebuild(dirty);
+        index.snapshot()
+    }
+
+    fn matched_id(entry: &MatchEntry, uri: &str) -> Option<String> {
+        entry
+            .match_host_uri(None, uri, |_| true)
+            .map(|(_, route)| route.inner.id.clone())
+    }
+
+    #[test]
+    fn test_regex_and_prefix_tiers_precedence() {
+        let entry = build_entry([
+            test_route("exact", &["/v1/users/42"], 0),
+            test_route("prefix_short", &["^~/v1/"], 0),
+            test_route("prefix_long", &["^~/v1/users/"], 0),
+            test_route("regex", &[r"~^/v[0-9]+/users/(\d+)$"], 0),
+            test_route("regex_ci", &[r"~*^/LEGACY/(?P<name>[a-z]+)$"], 0),
+        ]);
+
+        // matchit tier wins over prefix and regex
+        assert_eq!(matched_id(&entry, "/v1/users/42").as_deref(), Some("exact"));
+        // longest prefix wins over shorter prefix and regex
+        assert_eq!(
+            matched_id(&entry, "/v1/users/7").as_deref(),
+            Some("prefix_long")
+        );
+        assert_eq!(
+            matched_id(&entry, "/v1/orders").as_deref(),
+            Some("prefix_short")
+        );
+        // regex tier when nothing else matches
+        assert_eq!(matched_id(&entry, "/v2/users/7").as_deref(), Some("regex"));
+        assert_eq!(
+            matched_id(&entry, "/legacy/abc").as_deref(),
+            Some("regex_ci")
+        );
+        assert_eq!(matched_id(&entry, "/v2/orders"), None);
+    }
+
+    #[test]
+    fn test_tier_priority_order() {
+        let entry = build_entry([
+            test_route("a_exact", &["/orders"], 0),
+            test_route("b_exact", &["/orders"], 5),
+            test_route("c_exact", &["/orders"], 5),
+            test_route("a_prefix", &["^~/api/"], 0),
+            test_route("b_prefix", &["^~/api/"], 5),
+            test_route("a_regex", &[r"~^/v[0-9]+/"], 0),
+            test_route("b_regex", &[r"~^/v[0-9]+/"], 5),
+        ]);
+
+        // Higher priority wins within a tier, and ties go to the lower id
+        assert_eq!(matched_id(&entry, "/orders").as_deref(), Some("b_exact"));
+        assert_eq!(matched_id(&entry, "/api/x").as_deref(), Some("b_prefix"));
+        assert_eq!(matched_id(&entry, "/v2/x").as_deref(), Some("b_regex"));
+    }
+
+    #[test]
+    fn test_regex_captures_exposed_as_params() {
+        let entry = build_entry([test_route(
+            "regex",
+            &[r"~^/v(?P<version>[0-9]+)/users/(\d+)$"],
+            0,
+        )]);
+
+        let (params, _) = entry
+            .match_host_uri(None, "/v2/users/42", |_| true)
+            .unwrap();
+        assert_eq!(
+            params,
+            vec![
+                ("version".to_string(), "2".to_string()),
+                ("2".to_string(), "42".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejected_candidate_falls_through_to_next_tier() {
+        let entry = build_entry([
+            test_route("exact", &["/api/users"], 10),
+            test_route("regex_low", &["~^/api/"], 1),
+            test_route("regex_high", &["~^/api/users"], 5),
+        ]);
+
+        let result = entry.match_host_uri(None, "/api/users", |r| r.inner.id != "exact");
+        assert_eq!(result.unwrap().1.inner.id, "regex_high");
+    }
+
+    #[test]
+    fn test_route_connection_predicates() {
+        let route_cfg = config::Route {
+            remote_addrs: vec!["10.0.0.0/8".parse().unwrap()],
+            listeners: vec!["0.0.0.0:9080".parse().unwrap()],
+            scheme: Some(config::RouteScheme::HTTPS),
+            ..test_route("internal", &["/internal"], 0).inner.clone()
+        };
+        let route = ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap();
+
+        let internal_ip = Some("10.1.2.3".parse().unwrap());
+        let internal_listener = Some("172.16.0.5:9080".parse().unwrap());
+        assert!(route.accepts_connection(internal_ip, internal_listener, true));
+
+        // Wrong client network, missing client address, wrong listener, plain http
+        assert!(!route.accepts_connection(
+            Some("192.168.1.1".parse().unwrap()),
+            internal_listener,
+            true
+        ));
+        assert!(!route.accepts_connection(None, internal_listener, true));
+        assert!(!route.accepts_connection(
+            internal_ip,
+            Some("172.16.0.5:8080".parse().unwrap()),
+            true
+        ));
+        assert!(!route.accepts_connection(internal_ip, internal_listener, false));
+
+        // Unrestricted routes accept every connection
+        let open = test_route("open", &["/"], 0);
+        assert!(open.accepts_connection(None, None, false));
+    }
+
+    fn test_host_route(id: &str, host: &str, uri: &str) -> Arc<ProxyRoute> {
+        let route_cfg = config::Route {
+            hosts: vec![host.to_string()],
+            ..test_route(id, &[uri], 0).inner.clone()
+        };
+        Arc::new(ProxyRoute::new_with_upstream_and_plugins(route_cfg).unwrap())
+    }
+
+    #[test]
+    fn test_route_match_index_incremental_updates() {
+        let mut index = RouteMatchIndex::default();
+        let mut dirty = HashSet::new();
+        for route in [
+            test_host_route("api", "api.example.com", "/users"),
+            test_host_route("wildcard", "*.example.com", "/users"),
+            test_route("plain", &["/users"], 0),
+        ] {
+            let id = route.inner.id.clone();
+            index.apply(&id, Some(route), &mut dirty);
+        }
+        index.rebuild(dirty);
+        let before = index.snapshot();
+
+        let host_id = |entry: &MatchEntry, host: &str| {
+            entry
+                .match_host_uri(Some(host), "/users", |_| true)
+                .map(|(_, route)| route.inner.id.clone())
+        };
+        // Exact host routes are kept apart from the wildcard router
+        assert_eq!(host_id(&before, "api.example.com").as_deref(), Some("api"));
+        assert_eq!(
+            host_id(&before, "www.example.com").as_deref(),
+            Some("wildcard")
+        );
+        assert_eq!(host_id(&before, "other.com").as_deref(), Some("plain"));
+
+        // Moving a route to another host only rebuilds the old and new host routers
+        let mut dirty = HashSet::new();
+        index.apply(
+            "api",
+            Some(test_host_route("api", "new.example.com", "/users")),
+            &mut dirty,
+        );
+        assert_eq!(dirty.len(), 2);
+        index.rebuild(dirty);
+        let after = index.snapshot();
+        assert!(Arc::ptr_eq(&before.non_host_uri, &after.non_host_uri));
+        assert_eq!(
+            host_id(&after, "api.example.com").as_deref(),
+            Some("wildcard")
+        );
+        assert_eq!(host_id(&after, "new.example.com").as_deref(), Some("api"));
+
+        // Deleting and disabling both drop the route from the matcher
+        let disabled_cfg = config::Route {
+            status: config::ResourceStatus::Disabled,
+            ..test_route("plain", &["/users"], 0).inner.clone()
+        };
+        let disabled = Arc::new(ProxyRoute::new_with_upstream_and_plugins(disabled_cfg).unwrap());
+        let mut dirty = HashSet::new();
+        index.apply("wildcard", None, &mut dirty);
+        index.apply("plain", Some(disabled), &mut dirty);
+        index.rebuild(dirty);
+        let removed = index.snapshot();
+        assert_eq!(host_id(&removed, "www.example.com"), None);
+        assert_eq!(host_id(&removed, "other.com"), None);
+        assert_eq!(host_id(&removed, "new.example.com").as_deref(), Some("api"));
+    }
 }
@@ -0,0 +1,227 @@
+use std::{net::IpAddr, sync::Arc};
+
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
+use ipnetwork::IpNetwork;
+use once_cell::sync::Lazy;
+
+use crate::{
+    config::{self, Identifiable},
+    core::{ErrorContext, ProxyResult},
+};
+
+use super::{
+    upstream::{upstream_fetch, ProxyUpstream},
+    MapOperations,
+};
+
+/// Proxy stream route.
+///
+/// Forwards L4 connections and datagrams to an upstream.
+pub struct ProxyStreamRoute {
+    pub inner: config::StreamRoute,
+    pub upstream: Option<Arc<ProxyUpstream>>,
+    /// Client networks allowed to match, from `remote_addr`/`remote_addrs`.
+    remote_addrs: Vec<IpNetwork>,
+}
+
+impl Identifiable for ProxyStreamRoute {
+    fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.inner.id = id;
+    }
+}
+
+impl ProxyStreamRoute {
+    pub fn new_with_upstream(route: config::StreamRoute) -> ProxyResult<Self> {
+        let mut proxy_route = ProxyStreamRoute {
+            inner: route.clone(),
+            upstream: None,
+            remote_addrs: route.get_remote_addrs(),
+        };
+
+        if let Some(upstream_config) = route.upstream {
+            let proxy_upstream =
+                ProxyUpstream::new_with_shared_health_check(upstream_config).with_context(
+                    &format!("Failed to create upstream for stream route '{}'", route.id),
+                )?;
+            proxy_route.upstream = Some(Arc::new(proxy_upstream));
+        }
+
+        Ok(proxy_route)
+    }
+
+    /// Checks listener port, client address and SNI restrictions of the route.
+    ///
+    /// `sni` is `None` for UDP and for TCP connections that did not start with a
+    /// TLS ClientHello; routes with `sni` never match those.
+    fn accepts(&self, server_port: u16, client_ip: Option<IpAddr>, sni: Option<&str>) -> bool {
+        if !self.inner.status.is_enabled() {
+            return false;
+        }
+
+        if self
+            .inner
+            .server_port
+            .is_some_and(|port| port != server_port)
+        {
+            return false;
+        }
+
+        if !self.remote_addrs.is_empty()
+            && !client_ip.is_some_and(|ip| self.remote_addrs.iter().any(|n| n.contains(ip)))
+        {
+            return false;
+        }
+
+        match &self.inner.sni {
+            Some(pattern) => sni.is_some_and(|sni| sni_matches(pattern, sni)),
+            None => true,
+        }
+    }
+
+    /// Resolves the inline upstream, falling back to `upstream_id`.
+    pub fn resolve_upstream(&self) -> Option<Arc<ProxyUpstream>> {
+        self.upstream
+            .clone()
+            .or_else(|| self.inner.upstream_id.as_deref().and_then(upstream_fetch))
+    }
+
+    /// Number of match conditions, used to try more specific routes first.
+    fn specificity(&self) -> usize {
+        [
+            self.inner.sni.is_some(),
+            self.inner.server_port.is_some(),
+            !self.remote_addrs.is_empty(),
+        ]
+        .into_iter()
+        .filter(|set| *set)
+        .count()
+    }
+}
+
+/// Matches a server name against `pattern`, where `*.example.com` matches a single
+/// label below `example.com`.
+fn sni_matches(pattern: &str, sni: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => sni
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest.eq_ignore_ascii_case(suffix)),
+        None => pattern.eq_ignore_ascii_case(sni),
+    }
+}
+
+/// Global map to store stream routes, initialized lazily.
+pub static STREAM_ROUTE_MAP: Lazy<DashMap<String, Arc<ProxyStreamRoute>>> = Lazy::new(DashMap::new);
+
+/// Stream routes in match order: most specific first, then by ID.
+static GLOBAL_STREAM_ROUTE_MATCH: Lazy<ArcSwap<Vec<Arc<ProxyStreamRoute>>>> =
+    Lazy::new(|| ArcSwap::new(Arc::new(Vec::new())));
+
+pub fn reload_global_stream_route_match() {
+    let mut routes: Vec<Arc<ProxyStreamRoute>> = STREAM_ROUTE_MAP
+        .iter()
+        .map(|route| route.value().clone())
+        .collect();
+    routes.sort_by(|a, b| {
+        b.specificity()
+            .cmp(&a.specificity())
+            .then_with(|| a.inner.id.cmp(&b.inner.id))
+    });
+
+    GLOBAL_STREAM_ROUTE_MATCH.store(Arc::new(routes));
+}
+
+/// Finds the first stream route accepting the connection.
+pub fn stream_route_match(
+    server_port: u16,
+    client_ip: Option<IpAddr>,
+    sni: Option<&str>,
+) -> Option<Arc<ProxyStreamRoute>> {
+    GLOBAL_STREAM_ROUTE_MATCH
+        .load()
+        .iter()
+        .find(|route| route.accepts(server_port, client_ip, sni))
+        .cloned()
+}
+
+/// Whether any route on `server_port` matches on SNI, so the ClientHello must be read
+/// before a route can be chosen.
+pub fn stream_routes_need_sni(server_port: u16) -> bool {
+    GLOBAL_STREAM_ROUTE_MATCH.load().iter().any(|route| {
+        route.inner.sni.is_some()
+            && route
+                .inner
+                .server_port
+                .is_none_or(|port| port == server_port)
+    })
+}
+
+/// Loads stream routes from the given configuration.
+pub fn load_static_stream_routes(config: &config::Config) -> ProxyResult<()> {
+    let proxy_routes: Vec<Arc<ProxyStreamRoute>> = config
+        .stream_routes
+        .iter()
+        .map(|route| {
+            log::info!("Configuring StreamRoute: {}", route.id);
+            match ProxyStreamRoute::new_with_upstream(route.clone()) {
+                Ok(proxy_route) => Ok(Arc::new(proxy_route)),
+                Err(e) => {
+                    log::error!("Failed to configure StreamRoute {}: {}", route.id, e);
+                    Err(e)
+                }
+            }
+        })
+        .collect::<ProxyResult<Vec<_>>>()?;
+
+    STREAM_ROUTE_MAP.reload_resources(proxy_routes);
+    reload_global_stream_route_match();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_route(id: &str, yaml: &str) -> ProxyStreamRoute {
+        let mut route: config::StreamRoute = serde_yaml::from_str(yaml).unwrap();
+        route.id = id.to_string();
+        ProxyStreamRoute {
+            remote_addrs: route.get_remote_addrs(),
+            inner: route,
+            upstream: None,
+        }
+    }
+
+    #[test]
+    fn test_stream_route_accepts() {
+        let route = stream_route(
+            "1",
+            "server_port: 9100\nremote_addrs: [\"10.0.0.0/8\"]\nupstream_id: \"1\"",
+        );
+        let client = "10.1.2.3".parse().ok();
+        assert!(route.accepts(9100, client, None));
+        assert!(!route.accepts(9101, client, None));
+        assert!(!route.accepts(9100, "192.168.0.1".parse().ok(), None));
+
+        let route = stream_route("2", "sni: \"*.example.com\"\nupstream_id: \"1\"");
+        assert!(route.accepts(9100, client, Some("db.Example.com")));
+        assert!(!route.accepts(9100, client, Some("example.com")));
+        assert!(!route.accepts(9100, client, None));
+
+        let route = stream_route("3", "status: 0\nupstream_id: \"1\"");
+        assert!(!route.accepts(9100, client, None));
+    }
+
+    #[test]
+    fn test_sni_matches() {
+        assert!(sni_matches("db.example.com", "DB.example.com"));
+        assert!(sni_matches("*.example.com", "a.example.com"));
+        assert!(!sni_matches("*.example.com", "a.b.example.com"));
+        assert!(!sni_matches("*.example.com", ".example.com"));
+    }
+}
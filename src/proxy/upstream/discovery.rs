@@ -1,22 +1,38 @@
 use std::collections::{BTreeSet, HashMap};
 use std::net::{IpAddr, SocketAddr};
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use futures::future::join_all;
-use hickory_resolver::TokioResolver;
+use hickory_resolver::{proto::rr::rdata::SRV, TokioResolver};
 use once_cell::sync::OnceCell;
-use pingora::{protocols::ALPN, upstreams::peer::HttpPeer};
-use pingora_core::utils::tls::CertKey;
-use pingora_error::{Error, ErrorType::InternalError, OrErr, Result};
+use pingora::{
+    protocols::ALPN,
+    upstreams::peer::{HttpPeer, Peer},
+};
+use pingora_core::{connectors::http::Connector, protocols::tls::CaType, utils::tls::CertKey};
+use pingora_error::{
+    Error,
+    ErrorType::{self, InternalError},
+    OkOrErr, OrErr, Result,
+};
+use pingora_http::RequestHeader;
 use pingora_load_balancing::{
     discovery::{ServiceDiscovery, Static},
-    Backend,
+    Backend, Extensions,
 };
 use regex::Regex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use validator::Validate;
 
 use crate::{
-    config::{Upstream, UpstreamPassHost, UpstreamScheme, UpstreamTls},
+    config::{
+        DiscoveryArgs, DiscoveryType, NodeMetadata, Upstream, UpstreamHttpVersion, UpstreamNodes,
+        UpstreamPassHost, UpstreamScheme, UpstreamTls,
+    },
     core::{ProxyError, ProxyResult},
 };
 
@@ -38,12 +54,12 @@ fn get_global_resolver() -> Arc<TokioResolver> {
 ///
 /// This function parses the certificate chain and private key from PEM encoded strings
 /// and creates a CertKey object that can be used for mTLS authentication.
-fn load_client_cert_key(tls_config: &UpstreamTls) -> ProxyResult<Arc<CertKey>> {
+fn load_client_cert_key(client_cert: &str, client_key: &str) -> ProxyResult<Arc<CertKey>> {
     use pingora_core::tls::pkey::PKey;
     use pingora_core::tls::x509::X509;
 
     // Parse the certificate chain
-    let cert_pem = tls_config.client_cert.as_bytes();
+    let cert_pem = client_cert.as_bytes();
     let certificates = X509::stack_from_pem(cert_pem)
         .or_err_with(pingora_error::ErrorType::InternalError, || {
             "Failed to parse client certificate PEM"
@@ -56,7 +72,7 @@ fn load_client_cert_key(tls_config: &UpstreamTls) -> ProxyResult<Arc<CertKey>> {
     }
 
     // Parse the private key
-    let key_pem = tls_config.client_key.as_bytes();
+    let key_pem = client_key.as_bytes();
     let private_key = PKey::private_key_from_pem(key_pem)
         .or_err_with(pingora_error::ErrorType::InternalError, || {
             "Failed to parse client private key PEM"
@@ -68,6 +84,117 @@ fn load_client_cert_key(tls_config: &UpstreamTls) -> ProxyResult<Arc<CertKey>> {
     Ok(Arc::new(cert_key))
 }
 
+/// Loads a bundle of CA certificates from a PEM format string.
+fn load_ca(ca: &str) -> ProxyResult<Arc<CaType>> {
+    use pingora_core::tls::x509::X509;
+
+    let certificates = X509::stack_from_pem(ca.as_bytes())
+        .or_err_with(InternalError, || "Failed to parse CA certificate PEM")?;
+
+    if certificates.is_empty() {
+        return Err(ProxyError::Configuration(
+            "No certificates found in ca".to_string(),
+        ));
+    }
+
+    Ok(Arc::new(certificates.into_boxed_slice()))
+}
+
+/// TLS settings of an upstream, applied to the `HttpPeer` of each of its nodes.
+pub struct PeerTls {
+    client_cert_key: Option<Arc<CertKey>>,
+    ca: Option<Arc<CaType>>,
+    sni: Option<String>,
+    verify_cert: bool,
+    verify_hostname: bool,
+}
+
+impl PeerTls {
+    /// Parses the certificates of the upstream TLS configuration.
+    pub fn new(tls_config: &UpstreamTls) -> ProxyResult<Self> {
+        let client_cert_key = match (&tls_config.client_cert, &tls_config.client_key) {
+            (Some(cert), Some(key)) => Some(load_client_cert_key(cert, key)?),
+            _ => None,
+        };
+
+        Ok(Self {
+            client_cert_key,
+            ca: tls_config.ca.as_deref().map(load_ca).transpose()?,
+            sni: tls_config.sni.clone(),
+            verify_cert: tls_config.verify,
+            verify_hostname: tls_config.verify_hostname.unwrap_or(tls_config.verify),
+        })
+    }
+
+    fn apply(&self, peer: &mut HttpPeer) {
+        peer.client_cert_key = self.client_cert_key.clone();
+        if let Some(sni) = &self.sni {
+            peer.sni = sni.clone();
+        }
+        peer.options.ca = self.ca.clone();
+        peer.options.verify_cert = self.verify_cert;
+        peer.options.verify_hostname = self.verify_hostname;
+    }
+}
+
+/// HTTP version and keepalive settings of an upstream, applied to the `HttpPeer` of each of its
+/// nodes over the defaults of its scheme.
+#[derive(Clone, Default)]
+struct PeerConnection {
+    alpn: Option<ALPN>,
+    idle_timeout: Option<Duration>,
+}
+
+impl PeerConnection {
+    fn new(upstream: &Upstream) -> Self {
+        let grpc = matches!(
+            upstream.scheme,
+            UpstreamScheme::GRPC | UpstreamScheme::GRPCS
+        );
+        let alpn = upstream.http_version.map(|version| match version {
+            UpstreamHttpVersion::H1 => ALPN::H1,
+            // gRPC cannot fall back to HTTP/1.1
+            UpstreamHttpVersion::H2 if grpc => ALPN::H2,
+            UpstreamHttpVersion::H2 => ALPN::H2H1,
+            // Without TLS, an HTTP/2 only peer speaks h2c with prior knowledge
+            UpstreamHttpVersion::H2C => ALPN::H2,
+        });
+
+        Self {
+            alpn,
+            idle_timeout: upstream
+                .keepalive_pool
+                .as_ref()
+                .map(|pool| Duration::from_secs(pool.idle_timeout)),
+        }
+    }
+
+    fn apply(&self, backend: &mut Backend) {
+        let Some(peer) = backend.ext.get_mut::<HttpPeer>() else {
+            return;
+        };
+        if let Some(alpn) = &self.alpn {
+            peer.options.alpn = alpn.clone();
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            peer.options.idle_timeout = Some(idle_timeout);
+        }
+    }
+}
+
+/// Creates the `HttpPeer` of a discovered node.
+fn new_peer(addr: &str, scheme: UpstreamScheme, sni: String, tls: Option<&PeerTls>) -> HttpPeer {
+    let use_tls = matches!(scheme, UpstreamScheme::HTTPS | UpstreamScheme::GRPCS);
+    let mut peer = HttpPeer::new(addr, use_tls, sni);
+    if matches!(scheme, UpstreamScheme::GRPC | UpstreamScheme::GRPCS) {
+        peer.options.alpn = ALPN::H2;
+    }
+    if let Some(tls) = tls {
+        tls.apply(&mut peer);
+    }
+    peer
+}
+
 /// DNS-based service discovery.
 ///
 /// Resolves DNS names to IP addresses and creates backends for each resolved IP.
@@ -77,7 +204,8 @@ pub struct DnsDiscovery {
     port: u32,
     scheme: UpstreamScheme,
     weight: u32,
-    client_cert_key: Option<Arc<CertKey>>,
+    tls: Option<Arc<PeerTls>>,
+    metadata: Option<Arc<NodeMetadata>>,
 }
 
 impl DnsDiscovery {
@@ -88,7 +216,8 @@ impl DnsDiscovery {
         scheme: UpstreamScheme,
         weight: u32,
         resolver: Arc<TokioResolver>,
-        client_cert_key: Option<Arc<CertKey>>,
+        tls: Option<Arc<PeerTls>>,
+        metadata: Option<Arc<NodeMetadata>>,
     ) -> Self {
         Self {
             resolver,
@@ -96,7 +225,8 @@ impl DnsDiscovery {
             port,
             scheme,
             weight,
-            client_cert_key,
+            tls,
+            metadata,
         }
     }
 }
@@ -142,14 +272,18 @@ impl ServiceDiscovery for DnsDiscovery {
                     peer.options.alpn = ALPN::H2;
                 }
 
-                // Set client certificate if configured
-                if let Some(ref cert_key) = self.client_cert_key {
-                    peer.client_cert_key = Some(cert_key.clone());
+                // Apply client certificate and verification settings if configured
+                if let Some(tls) = &self.tls {
+                    tls.apply(&mut peer);
                 }
 
                 // Insert HttpPeer into the backend
                 debug_assert!(backend.ext.insert::<HttpPeer>(peer).is_none());
 
+                if let Some(metadata) = &self.metadata {
+                    backend.ext.insert(metadata.clone());
+                }
+
                 Some(backend)
             })
             .collect();
@@ -159,12 +293,467 @@ impl ServiceDiscovery for DnsDiscovery {
     }
 }
 
+/// Priority of a discovered node, attached to its `Backend.ext`.
+///
+/// Like the `priority` of configured nodes, nodes only get traffic when no node of a higher
+/// priority is healthy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodePriority(pub i32);
+
+/// DNS SRV-based service discovery.
+///
+/// Resolves the SRV records of a service and the addresses of their targets, creating a
+/// backend for each address with the port, weight and priority of the record.
+pub struct SrvDiscovery {
+    resolver: Arc<TokioResolver>,
+    service_name: String,
+    scheme: UpstreamScheme,
+    tls: Option<Arc<PeerTls>>,
+}
+
+impl SrvDiscovery {
+    /// Creates a new `SrvDiscovery` instance.
+    pub fn new(
+        service_name: String,
+        scheme: UpstreamScheme,
+        resolver: Arc<TokioResolver>,
+        tls: Option<Arc<PeerTls>>,
+    ) -> Self {
+        Self {
+            resolver,
+            service_name,
+            scheme,
+            tls,
+        }
+    }
+
+    /// Creates the backend of an address the target of `srv` resolved to.
+    fn backend(&self, srv: &SRV, ip: IpAddr) -> Option<Backend> {
+        let addr = SocketAddr::new(ip, srv.port()).to_string();
+
+        // SRV weight 0 is meant to be picked rarely rather than never
+        let weight = srv.weight().max(1);
+        let mut backend = match Backend::new_with_weight(&addr, weight as _) {
+            Ok(b) => b,
+            Err(e) => {
+                log::error!("Failed to create backend for {addr}: {e}");
+                return None;
+            }
+        };
+
+        let sni = srv.target().to_utf8().trim_end_matches('.').to_string();
+        let peer = new_peer(&addr, self.scheme, sni, self.tls.as_deref());
+        backend.ext.insert(peer);
+
+        // Lower SRV priority values are preferred, higher node priorities are
+        backend.ext.insert(NodePriority(-i32::from(srv.priority())));
+
+        Some(backend)
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for SrvDiscovery {
+    /// Discovers backends by resolving the SRV records of the service and their targets.
+    async fn discover(&self) -> Result<(BTreeSet<Backend>, HashMap<u64, bool>)> {
+        let service_name = self.service_name.as_str();
+        log::debug!("Resolving DNS SRV for service: {service_name}");
+
+        let records = self.resolver.srv_lookup(service_name).await.map_err(|e| {
+            log::warn!("DNS SRV discovery failed for service: {service_name}: {e}");
+            Error::because(
+                InternalError,
+                format!("DNS SRV discovery failed for service: {service_name}: {e}"),
+                e,
+            )
+        })?;
+
+        // A target of "." means the service is not available
+        let records: Vec<&SRV> = records
+            .iter()
+            .filter(|srv| !srv.target().is_root())
+            .collect();
+
+        let addresses = join_all(records.iter().map(|srv| async move {
+            match self.resolver.lookup_ip(srv.target().clone()).await {
+                Ok(ips) => ips.iter().collect(),
+                Err(e) => {
+                    log::warn!("Failed to resolve SRV target {}: {e}", srv.target());
+                    Vec::new()
+                }
+            }
+        }))
+        .await;
+
+        let backends = records
+            .iter()
+            .zip(addresses)
+            .flat_map(|(srv, ips)| {
+                ips.into_iter()
+                    .filter_map(|ip: IpAddr| self.backend(srv, ip))
+            })
+            .collect();
+
+        Ok((backends, HashMap::new()))
+    }
+}
+
+/// Timeout of connecting to and reading from the Consul HTTP API.
+const CONSUL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Largest Consul response read, to bound memory use.
+const CONSUL_MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Consul catalog service discovery.
+///
+/// Polls the Consul health API for the passing instances of a service, creating a backend for
+/// each with the instance's port, passing weight and `Meta` as node metadata.
+pub struct ConsulDiscovery {
+    args: DiscoveryArgs,
+    /// TLS settings of the Consul API connection, when reached over HTTPS.
+    consul_tls: Option<PeerTls>,
+    /// Path and query of the health API request.
+    path: String,
+    scheme: UpstreamScheme,
+    tls: Option<Arc<PeerTls>>,
+    connector: Connector,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulServiceEntry {
+    node: ConsulNode,
+    service: ConsulService,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulNode {
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulService {
+    /// Empty when the instance uses the address of its node.
+    #[serde(default)]
+    address: String,
+    port: u16,
+    weights: Option<ConsulWeights>,
+    meta: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulWeights {
+    passing: u32,
+}
+
+impl ConsulDiscovery {
+    /// Creates a new `ConsulDiscovery` for the instances of `service_name`.
+    pub fn new(
+        service_name: &str,
+        args: DiscoveryArgs,
+        scheme: UpstreamScheme,
+        tls: Option<Arc<PeerTls>>,
+    ) -> ProxyResult<Self> {
+        let mut path = format!(
+            "/v1/health/service/{}?passing=true",
+            encode_query_component(service_name)
+        );
+        for tag in &args.tags {
+            path.push_str(&format!("&tag={}", encode_query_component(tag)));
+        }
+        if let Some(datacenter) = &args.datacenter {
+            path.push_str(&format!("&dc={}", encode_query_component(datacenter)));
+        }
+
+        Ok(Self {
+            consul_tls: args.tls.as_ref().map(PeerTls::new).transpose()?,
+            args,
+            path,
+            scheme,
+            tls,
+            connector: Connector::new(None),
+        })
+    }
+
+    /// Fetches the passing instances of the service.
+    async fn fetch(&self) -> Result<Vec<ConsulServiceEntry>> {
+        let (host, port) = parse_host_and_port(&self.args.address)
+            .or_err(InternalError, "Invalid Consul address")?;
+        let ip = get_global_resolver()
+            .lookup_ip(host.as_str())
+            .await
+            .or_err_with(InternalError, || {
+                format!("Failed to resolve Consul host {host}")
+            })?
+            .iter()
+            .next()
+            .or_err_with(InternalError, || {
+                format!("No address for Consul host {host}")
+            })?;
+        let mut peer = HttpPeer::new(
+            SocketAddr::new(ip, port.unwrap_or(self.args.default_port().into()) as _),
+            self.consul_tls.is_some(),
+            host.clone(),
+        );
+        if let Some(tls) = &self.consul_tls {
+            tls.apply(&mut peer);
+        }
+        peer.options.connection_timeout = Some(CONSUL_TIMEOUT);
+        peer.options.read_timeout = Some(CONSUL_TIMEOUT);
+
+        let mut req = RequestHeader::build("GET", self.path.as_bytes(), None)?;
+        req.insert_header(http::header::HOST, self.args.address.as_str())?;
+        if let Some(token) = &self.args.token {
+            req.insert_header("X-Consul-Token", token.as_str())?;
+        }
+
+        let (mut session, _) = self.connector.get_http_session(&peer).await?;
+        session.set_read_timeout(Some(CONSUL_TIMEOUT));
+        session.write_request_header(Box::new(req)).await?;
+        session.finish_request_body().await?;
+        session.read_response_header().await?;
+        let status = session.response_header().expect("just read").status;
+
+        let mut body = Vec::new();
+        while let Some(chunk) = session.read_response_body().await? {
+            if body.len() + chunk.len() > CONSUL_MAX_RESPONSE_SIZE {
+                return Error::e_explain(InternalError, "Consul response too large");
+            }
+            body.extend_from_slice(&chunk);
+        }
+        self.connector
+            .release_http_session(session, &peer, peer.idle_timeout())
+            .await;
+
+        if status != 200 {
+            return Error::e_explain(
+                ErrorType::HTTPStatus(status.as_u16()),
+                format!(
+                    "Consul returned {status}: {}",
+                    String::from_utf8_lossy(&body)
+                ),
+            );
+        }
+
+        serde_json::from_slice(&body).or_err(InternalError, "Invalid Consul response")
+    }
+
+    /// Creates the backend of a passing service instance.
+    fn backend(&self, entry: ConsulServiceEntry) -> Option<Backend> {
+        let ConsulServiceEntry { node, service } = entry;
+        let host = if service.address.is_empty() {
+            node.address
+        } else {
+            service.address
+        };
+        let addr = match host.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, service.port).to_string(),
+            Err(_) => {
+                log::warn!("Skipping Consul instance with non-IP address {host}");
+                return None;
+            }
+        };
+
+        let weight = service.weights.map_or(1, |weights| weights.passing.max(1));
+        let mut backend = match Backend::new_with_weight(&addr, weight as _) {
+            Ok(b) => b,
+            Err(e) => {
+                log::error!("Failed to create backend for {addr}: {e}");
+                return None;
+            }
+        };
+
+        let peer = new_peer(&addr, self.scheme, host, self.tls.as_deref());
+        backend.ext.insert(peer);
+
+        if let Some(meta) = service.meta.filter(|meta| !meta.is_empty()) {
+            backend.ext.insert(Arc::new(NodeMetadata(meta)));
+        }
+
+        Some(backend)
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for ConsulDiscovery {
+    /// Discovers backends from the passing instances of the service in Consul.
+    async fn discover(&self) -> Result<(BTreeSet<Backend>, HashMap<u64, bool>)> {
+        let entries = self.fetch().await.map_err(|e| {
+            log::warn!("Consul discovery failed for {}: {e}", self.path);
+            e
+        })?;
+
+        let backends = entries
+            .into_iter()
+            .filter_map(|entry| self.backend(entry))
+            .collect();
+
+        Ok((backends, HashMap::new()))
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, for URL paths and query values.
+fn encode_query_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// File-based service discovery.
+///
+/// Reads nodes in the `nodes` format from a JSON file, reloading them whenever its contents
+/// change. An unreadable or invalid file keeps the previous nodes.
+pub struct FileDiscovery {
+    path: PathBuf,
+    upstream: Upstream,
+    /// Discoveries of the last valid file contents, per node priority.
+    loaded: Mutex<Option<LoadedFile>>,
+}
+
+struct LoadedFile {
+    /// SHA-256 of the file contents, as a rewrite may keep the modification time and size.
+    digest: [u8; 32],
+    tiers: Arc<Vec<(i32, HybridDiscovery)>>,
+}
+
+impl FileDiscovery {
+    /// Creates a new `FileDiscovery` for the nodes file of a discovery-based upstream.
+    pub fn new(path: PathBuf, upstream: &Upstream) -> Self {
+        Self {
+            path,
+            upstream: upstream.clone(),
+            loaded: Mutex::new(None),
+        }
+    }
+
+    /// Discoveries of the file's nodes, re-read if the file changed since the last call.
+    async fn tiers(&self) -> Result<Arc<Vec<(i32, HybridDiscovery)>>> {
+        let path = self.path.display();
+        let contents = tokio::fs::read(&self.path)
+            .await
+            .or_err_with(InternalError, || {
+                format!("Failed to read nodes file {path}")
+            })?;
+        let digest: [u8; 32] = Sha256::digest(&contents).into();
+
+        if let Some(loaded) = self
+            .loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            if loaded.digest == digest {
+                return Ok(loaded.tiers.clone());
+            }
+        }
+
+        let tiers =
+            Arc::new(self.parse(&contents).map_err(|e| {
+                Error::because(InternalError, format!("Invalid nodes file {path}"), e)
+            })?);
+        log::info!("Loaded nodes file {path}");
+
+        *self.loaded.lock().unwrap_or_else(|e| e.into_inner()) = Some(LoadedFile {
+            digest,
+            tiers: tiers.clone(),
+        });
+        Ok(tiers)
+    }
+
+    fn parse(&self, contents: &[u8]) -> ProxyResult<Vec<(i32, HybridDiscovery)>> {
+        let nodes: UpstreamNodes = serde_json::from_slice(contents)
+            .map_err(|e| ProxyError::Configuration(e.to_string()))?;
+
+        // Validate the nodes like configured ones, which also rejects an empty list
+        let upstream = Upstream {
+            nodes,
+            discovery_type: None,
+            service_name: None,
+            path: None,
+            ..self.upstream.clone()
+        };
+        upstream
+            .validate()
+            .map_err(|e| ProxyError::Configuration(e.to_string()))?;
+
+        upstream
+            .nodes
+            .priorities()
+            .into_iter()
+            .map(|priority| Ok((priority, HybridDiscovery::new(&upstream, priority)?)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for FileDiscovery {
+    /// Discovers backends from the nodes file, tagging them with their node priority.
+    async fn discover(&self) -> Result<(BTreeSet<Backend>, HashMap<u64, bool>)> {
+        let tiers = self.tiers().await?;
+
+        let mut backends = BTreeSet::new();
+        let mut health_checks = HashMap::new();
+        for (priority, discovery) in tiers.iter() {
+            let (tier_backends, tier_health_checks) = discovery.discover().await?;
+            backends.extend(tier_backends.into_iter().map(|mut backend| {
+                backend.ext.insert(NodePriority(*priority));
+                backend
+            }));
+            health_checks.extend(tier_health_checks);
+        }
+
+        Ok((backends, health_checks))
+    }
+}
+
+/// Nodes file for `file` discovery in tests, in the temp directory and removed when dropped.
+#[cfg(test)]
+pub(crate) struct TestNodesFile {
+    pub path: PathBuf,
+}
+
+#[cfg(test)]
+impl TestNodesFile {
+    /// A path for a nodes file that does not exist yet.
+    pub fn new() -> Self {
+        Self {
+            path: std::env::temp_dir().join(format!("pingsix-nodes-{}.json", uuid::Uuid::new_v4())),
+        }
+    }
+
+    pub fn write(&self, contents: &str) {
+        std::fs::write(&self.path, contents).unwrap();
+    }
+
+    /// Upstream config discovering the nodes of the file.
+    pub fn upstream_yaml(&self) -> String {
+        format!("discovery_type: file\npath: {}", self.path.display())
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestNodesFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
 /// Hybrid service discovery.
 ///
 /// Combines static and DNS-based service discovery.
 #[derive(Default)]
 pub struct HybridDiscovery {
     discoveries: Vec<Box<dyn ServiceDiscovery + Send + Sync>>,
+    connection: PeerConnection,
 }
 
 #[async_trait]
@@ -184,8 +773,19 @@ impl ServiceDiscovery for HybridDiscovery {
 
         let results = join_all(futures).await;
 
+        // Keep the previous backends when nothing could be discovered
+        if results.iter().all(Result::is_err) {
+            if let Some(Err(e)) = results.into_iter().next() {
+                return Err(e);
+            }
+            return Ok((backends, health_checks));
+        }
+
         for (part_backends, part_health_checks) in results.into_iter().flatten() {
-            backends.extend(part_backends);
+            backends.extend(part_backends.into_iter().map(|mut backend| {
+                self.connection.apply(&mut backend);
+                backend
+            }));
             health_checks.extend(part_health_checks);
         }
 
@@ -193,36 +793,115 @@ impl ServiceDiscovery for HybridDiscovery {
     }
 }
 
-impl TryFrom<Upstream> for HybridDiscovery {
-    type Error = ProxyError;
-
-    fn try_from(upstream: Upstream) -> ProxyResult<Self> {
-        let mut this = Self::default();
+impl HybridDiscovery {
+    /// Creates the discovery of the upstream's nodes with the given priority.
+    ///
+    /// Discovery-based upstreams have a single tier; their nodes carry a [`NodePriority`].
+    pub fn new(upstream: &Upstream, priority: i32) -> ProxyResult<Self> {
+        let mut this = Self {
+            discoveries: Vec::new(),
+            connection: PeerConnection::new(upstream),
+        };
         let mut backends = BTreeSet::new();
 
-        // Load client certificate if configured
-        let client_cert_key = if let Some(ref tls) = upstream.tls {
-            Some(load_client_cert_key(tls)?)
-        } else {
-            None
-        };
+        // Load TLS certificates if configured
+        let peer_tls = upstream
+            .tls
+            .as_ref()
+            .map(PeerTls::new)
+            .transpose()?
+            .map(Arc::new);
 
-        // Process each node in upstream
-        for (addr, weight) in upstream.nodes.iter() {
-            let (host, port) = parse_host_and_port(addr)?;
-            let port = port.unwrap_or(match upstream.scheme {
-                UpstreamScheme::HTTPS | UpstreamScheme::GRPCS => 443,
-                _ => 80,
-            });
-
-            // Strip brackets from IPv6 for parsing, then add them back for SocketAddr
-            let host_for_parse = if host.starts_with('[') && host.ends_with(']') {
-                &host[1..host.len() - 1]
-            } else {
-                host.as_str()
+        if let Some(discovery_type) = upstream.discovery_type {
+            let required = |field: &Option<String>, name: &str| {
+                field.clone().ok_or_else(|| {
+                    ProxyError::Configuration(format!("{name} is required for discovery"))
+                })
             };
+            match discovery_type {
+                DiscoveryType::DnsSrv => this.discoveries.push(Box::new(SrvDiscovery::new(
+                    required(&upstream.service_name, "service_name")?,
+                    upstream.scheme,
+                    get_global_resolver(),
+                    peer_tls,
+                ))),
+                DiscoveryType::File => this.discoveries.push(Box::new(FileDiscovery::new(
+                    required(&upstream.path, "path")?.into(),
+                    upstream,
+                ))),
+                DiscoveryType::Consul => this.discoveries.push(Box::new(ConsulDiscovery::new(
+                    &required(&upstream.service_name, "service_name")?,
+                    upstream.discovery_args.clone().unwrap_or_default(),
+                    upstream.scheme,
+                    peer_tls,
+                )?)),
+            }
+            return Ok(this);
+        }
+
+        // Process each node in the priority tier
+        for node in upstream.nodes.entries() {
+            if node.priority != priority {
+                continue;
+            }
+
+            let metadata = (!node.metadata.is_empty()).then(|| Arc::new(node.metadata.clone()));
+            let tls = matches!(
+                upstream.scheme,
+                UpstreamScheme::HTTPS | UpstreamScheme::GRPCS
+            );
+
+            let (mut backend, mut peer) = if let Some(path) = node.addr.strip_prefix("unix:") {
+                // A Unix domain socket has no host name, so the node host is `localhost`
+                let sni = match upstream.pass_host {
+                    UpstreamPassHost::REWRITE => upstream.upstream_host.clone(),
+                    _ => None,
+                }
+                .unwrap_or_else(|| "localhost".to_string());
+
+                let peer = HttpPeer::new_uds(path, tls, sni).map_err(|e| {
+                    ProxyError::Configuration(format!(
+                        "Failed to create backend for {}: {e}",
+                        node.addr
+                    ))
+                })?;
+                let backend = Backend {
+                    addr: peer._address.clone(),
+                    weight: node.weight as _,
+                    ext: Extensions::new(),
+                };
+                (backend, peer)
+            } else {
+                let (host, port) = parse_host_and_port(&node.addr)?;
+                let port = port.unwrap_or(match upstream.scheme {
+                    UpstreamScheme::HTTPS | UpstreamScheme::GRPCS => 443,
+                    _ => 80,
+                });
+
+                // Strip brackets from IPv6 for parsing, then add them back for SocketAddr
+                let host_for_parse = if host.starts_with('[') && host.ends_with(']') {
+                    &host[1..host.len() - 1]
+                } else {
+                    host.as_str()
+                };
+
+                let Ok(ip_addr) = host_for_parse.parse::<IpAddr>() else {
+                    // It's a domain name
+                    // Handle DNS discovery for domain names
+                    let resolver = get_global_resolver();
+                    let discovery = DnsDiscovery::new(
+                        host,
+                        port,
+                        upstream.scheme,
+                        node.weight,
+                        resolver,
+                        peer_tls.clone(),
+                        metadata,
+                    );
+                    this.discoveries.push(Box::new(discovery));
+                    continue;
+                };
 
-            if let Ok(ip_addr) = host_for_parse.parse::<IpAddr>() {
                 // It's an IP address
                 // Handle backend creation for IP addresses - add brackets for IPv6
                 let addr_str = if ip_addr.is_ipv6() {
@@ -230,17 +909,13 @@ impl TryFrom<Upstream> for HybridDiscovery {
                 } else {
                     format!("{ip_addr}:{port}")
                 };
-                let mut backend =
-                    Backend::new_with_weight(&addr_str, *weight as _).map_err(|e| {
+                let backend =
+                    Backend::new_with_weight(&addr_str, node.weight as _).map_err(|e| {
                         ProxyError::Configuration(format!(
                             "Failed to create backend for {addr_str}: {e}"
                         ))
                     })?;
 
-                let tls = matches!(
-                    upstream.scheme,
-                    UpstreamScheme::HTTPS | UpstreamScheme::GRPCS
-                );
                 let sni = if upstream.pass_host == UpstreamPassHost::REWRITE {
                     upstream
                         .upstream_host
@@ -249,37 +924,28 @@ impl TryFrom<Upstream> for HybridDiscovery {
                 } else {
                     host.to_string()
                 };
+                (backend, HttpPeer::new(&addr_str, tls, sni))
+            };
 
-                let mut peer = HttpPeer::new(&addr_str, tls, sni);
-                if matches!(
-                    upstream.scheme,
-                    UpstreamScheme::GRPC | UpstreamScheme::GRPCS
-                ) {
-                    peer.options.alpn = ALPN::H2;
-                }
+            if matches!(
+                upstream.scheme,
+                UpstreamScheme::GRPC | UpstreamScheme::GRPCS
+            ) {
+                peer.options.alpn = ALPN::H2;
+            }
 
-                // Set client certificate if configured
-                if let Some(ref cert_key) = client_cert_key {
-                    peer.client_cert_key = Some(cert_key.clone());
-                }
+            // Apply client certificate and verification settings if configured
+            if let Some(peer_tls) = &peer_tls {
+                peer_tls.apply(&mut peer);
+            }
 
-                debug_assert!(backend.ext.insert::<HttpPeer>(peer).is_none());
+            backend.ext.insert(peer);
 
-                backends.insert(backend);
-            } else {
-                // It's a domain name
-                // Handle DNS discovery for domain names
-                let resolver = get_global_resolver();
-                let discovery = DnsDiscovery::new(
-                    host,
-                    port,
-                    upstream.scheme,
-                    *weight,
-                    resolver,
-                    client_cert_key.clone(),
-                );
-                this.discoveries.push(Box::new(discovery));
+            if let Some(metadata) = metadata {
+                backend.ext.insert(metadata);
             }
+
+            backends.insert(backend);
         }
 
         if !backends.is_empty() {
@@ -328,7 +994,7 @@ fn parse_host_and_port(addr: &str) -> ProxyResult<(String, Option<u32>)> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_host_and_port;
+    use super::*;
 
     #[test]
     fn test_parse_upstream_node() {
@@ -355,4 +1021,242 @@ mod tests {
         assert!(parse_host_and_port("invalid:port").is_err());
         assert!(parse_host_and_port("127.0.0.1:invalid").is_err());
     }
+
+    #[test]
+    fn test_peer_tls_apply() {
+        let tls_config: UpstreamTls =
+            serde_yaml::from_str("sni: internal.example.com\nverify_hostname: false").unwrap();
+        let mut peer = HttpPeer::new("10.0.0.1:8443", true, "10.0.0.1".to_string());
+        PeerTls::new(&tls_config).unwrap().apply(&mut peer);
+        assert_eq!(peer.sni, "internal.example.com");
+        assert!(peer.options.verify_cert);
+        assert!(!peer.options.verify_hostname);
+
+        let tls_config: UpstreamTls = serde_yaml::from_str("verify: false").unwrap();
+        PeerTls::new(&tls_config).unwrap().apply(&mut peer);
+        assert!(!peer.options.verify_cert);
+        assert!(!peer.options.verify_hostname);
+
+        let tls_config: UpstreamTls = serde_yaml::from_str("ca: not a pem").unwrap();
+        assert!(PeerTls::new(&tls_config).is_err());
+    }
+
+    #[test]
+    fn test_srv_backend() {
+        use hickory_resolver::proto::rr::Name;
+
+        let discovery = SrvDiscovery::new(
+            "_https._tcp.svc.local".to_string(),
+            UpstreamScheme::HTTPS,
+            get_global_resolver(),
+            None,
+        );
+        let srv = SRV::new(10, 0, 8443, Name::from_ascii("node1.svc.local.").unwrap());
+        let backend = discovery
+            .backend(&srv, "10.0.0.1".parse().unwrap())
+            .unwrap();
+
+        assert_eq!(backend.addr.to_string(), "10.0.0.1:8443");
+        assert_eq!(backend.weight, 1);
+        assert_eq!(backend.ext.get::<NodePriority>(), Some(&NodePriority(-10)));
+        let peer = backend.ext.get::<HttpPeer>().unwrap();
+        assert_eq!(peer.sni, "node1.svc.local");
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_node() {
+        let upstream: Upstream = serde_yaml::from_str(
+            "nodes:\n  \"unix:/run/app.sock\": 3\n  \"127.0.0.1:8080\": 1\nscheme: grpc",
+        )
+        .unwrap();
+        let discovery = HybridDiscovery::new(&upstream, 0).unwrap();
+        let (backends, _) = discovery.discover().await.unwrap();
+
+        let backend = backends
+            .iter()
+            .find(|b| b.addr.as_unix().is_some())
+            .unwrap();
+        assert_eq!(backend.addr.to_string(), "/run/app.sock");
+        assert_eq!(backend.weight, 3);
+        let peer = backend.ext.get::<HttpPeer>().unwrap();
+        assert_eq!(peer._address, backend.addr);
+        assert_eq!(peer.sni, "localhost");
+        assert_eq!(peer.options.alpn, ALPN::H2);
+    }
+
+    #[tokio::test]
+    async fn test_node_metadata() {
+        let upstream: Upstream = serde_yaml::from_str(
+            "nodes:\n  - {host: 127.0.0.1, port: 8080, metadata: {zone: us-east-1a}}\n  - {host: 127.0.0.1, port: 8081}",
+        )
+        .unwrap();
+        let discovery = HybridDiscovery::new(&upstream, 0).unwrap();
+        let (backends, _) = discovery.discover().await.unwrap();
+        let zones: Vec<(String, Option<&str>)> = backends
+            .iter()
+            .map(|b| {
+                let zone = b
+                    .ext
+                    .get::<Arc<NodeMetadata>>()
+                    .and_then(|metadata| metadata.get("zone"));
+                (b.addr.to_string(), zone)
+            })
+            .collect();
+        assert_eq!(
+            zones,
+            vec![
+                ("127.0.0.1:8080".to_string(), Some("us-east-1a")),
+                ("127.0.0.1:8081".to_string(), None),
+            ]
+        );
+
+        // Map-form nodes carry no metadata
+        let upstream: Upstream = serde_yaml::from_str("nodes: {'127.0.0.1:8080': 1}").unwrap();
+        let discovery = HybridDiscovery::new(&upstream, 0).unwrap();
+        let (backends, _) = discovery.discover().await.unwrap();
+        assert!(backends
+            .iter()
+            .all(|b| b.ext.get::<Arc<NodeMetadata>>().is_none()));
+    }
+
+    async fn discovered_peer(upstream: &str) -> HttpPeer {
+        let upstream: Upstream = serde_yaml::from_str(upstream).unwrap();
+        let discovery = HybridDiscovery::new(&upstream, 0).unwrap();
+        let (backends, _) = discovery.discover().await.unwrap();
+        let backend = backends.into_iter().next().unwrap();
+        backend.ext.get::<HttpPeer>().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_peer_connection() {
+        // Scheme defaults
+        let peer = discovered_peer("nodes: {'127.0.0.1:8080': 1}\nscheme: https").await;
+        assert_eq!(peer.options.alpn, ALPN::H1);
+        assert_eq!(peer.options.idle_timeout, None);
+
+        let peer = discovered_peer(
+            "nodes: {'127.0.0.1:8080': 1}\nscheme: https\nhttp_version: h2\nkeepalive_pool: {idle_timeout: 30}",
+        )
+        .await;
+        assert_eq!(peer.options.alpn, ALPN::H2H1);
+        assert_eq!(peer.options.idle_timeout, Some(Duration::from_secs(30)));
+
+        let peer = discovered_peer("nodes: {'127.0.0.1:8080': 1}\nhttp_version: h2c").await;
+        assert!(!peer.is_tls());
+        assert_eq!(peer.options.alpn, ALPN::H2);
+    }
+
+    #[tokio::test]
+    async fn test_file_discovery() {
+        let file = TestNodesFile::new();
+        let upstream: Upstream = serde_yaml::from_str(&file.upstream_yaml()).unwrap();
+        let discovery = FileDiscovery::new(file.path.clone(), &upstream);
+        let addrs = |backends: &BTreeSet<Backend>| -> Vec<(String, Option<i32>)> {
+            backends
+                .iter()
+                .map(|b| {
+                    let priority = b.ext.get::<NodePriority>().map(|p| p.0);
+                    (b.addr.to_string(), priority)
+                })
+                .collect()
+        };
+
+        // Missing file
+        assert!(discovery.discover().await.is_err());
+
+        file.write(r#"{"127.0.0.1:8001": 1}"#);
+        let (backends, _) = discovery.discover().await.unwrap();
+        assert_eq!(
+            addrs(&backends),
+            vec![("127.0.0.1:8001".to_string(), Some(0))]
+        );
+
+        // A rewrite of the same size within the same modification time is still picked up
+        file.write(r#"{"127.0.0.1:8009": 1}"#);
+        let (backends, _) = discovery.discover().await.unwrap();
+        assert_eq!(
+            addrs(&backends),
+            vec![("127.0.0.1:8009".to_string(), Some(0))]
+        );
+
+        file.write(
+            r#"[{"host": "127.0.0.1", "port": 8002}, {"host": "127.0.0.1", "port": 8003, "priority": -1}]"#,
+        );
+        let (backends, _) = discovery.discover().await.unwrap();
+        assert_eq!(
+            addrs(&backends),
+            vec![
+                ("127.0.0.1:8002".to_string(), Some(0)),
+                ("127.0.0.1:8003".to_string(), Some(-1)),
+            ]
+        );
+
+        // Invalid contents fail discovery, so the load balancer keeps the previous nodes
+        file.write("[]");
+        assert!(discovery.discover().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_consul_discovery() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+
+            let body = r#"[
+                {"Node": {"Address": "10.0.0.1"},
+                 "Service": {"Address": "", "Port": 8080, "Weights": {"Passing": 3, "Warning": 1},
+                             "Meta": {"zone": "a"}}},
+                {"Node": {"Address": "10.0.0.1"},
+                 "Service": {"Address": "10.0.0.2", "Port": 8081, "Meta": null}}
+            ]"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let args = DiscoveryArgs {
+            address,
+            tags: vec!["primary".to_string(), "v 2".to_string()],
+            token: Some("secret".to_string()),
+            datacenter: None,
+            tls: None,
+        };
+        let discovery = ConsulDiscovery::new("web", args, UpstreamScheme::HTTP, None).unwrap();
+        let (backends, _) = discovery.discover().await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request
+            .starts_with("GET /v1/health/service/web?passing=true&tag=primary&tag=v%202 HTTP/1.1"));
+        assert!(request.to_lowercase().contains("x-consul-token: secret"));
+
+        let nodes: Vec<(String, usize, Option<&str>)> = backends
+            .iter()
+            .map(|b| {
+                let zone = b
+                    .ext
+                    .get::<Arc<NodeMetadata>>()
+                    .and_then(|metadata| metadata.get("zone"));
+                (b.addr.to_string(), b.weight, zone)
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("10.0.0.1:8080".to_string(), 3, Some("a")),
+                ("10.0.0.2:8081".to_string(), 1, None),
+            ]
+        );
+    }
 }
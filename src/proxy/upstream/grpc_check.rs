@@ -0,0 +1,249 @@
+use async_trait::async_trait;
+use bytes::{BufMut, Bytes, BytesMut};
+use http::header;
+use pingora_core::{
+    connectors::http::Connector, protocols::http::client::HttpSession, upstreams::peer::HttpPeer,
+};
+use pingora_error::{Error, ErrorType, Result};
+use pingora_http::RequestHeader;
+use pingora_load_balancing::{health_check::HealthCheck as HealthCheckTrait, Backend};
+
+use super::http_check::node_host;
+
+/// Method of the standard gRPC health checking protocol.
+const HEALTH_CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+/// `SERVING` of `grpc.health.v1.HealthCheckResponse.ServingStatus`.
+const SERVING: u64 = 1;
+/// Largest health check response read.
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+/// Error of probes the node answered but failed.
+const CHECK_FAILED: ErrorType = ErrorType::Custom("GrpcHealthCheckFailed");
+
+/// Active health check calling `grpc.health.v1.Health/Check` over HTTP/2.
+///
+/// A node is healthy when it answers with `grpc-status` 0 and the `SERVING` status.
+pub struct GrpcHealthCheck {
+    /// Number of successful checks to flip from unhealthy to healthy.
+    pub consecutive_success: usize,
+    /// Number of failed checks to flip from healthy to unhealthy.
+    pub consecutive_failure: usize,
+    /// How to connect to the node; its address is replaced by the node's.
+    pub peer_template: HttpPeer,
+    /// Port to check instead of the node's.
+    pub port_override: Option<u16>,
+    /// Request header; without a `Host` header the node's address is the authority.
+    pub req: RequestHeader,
+    body: Bytes,
+    connector: Connector,
+}
+
+impl GrpcHealthCheck {
+    /// Creates a check of `service`, where an empty name checks the server as a whole.
+    pub fn new(host: &str, tls: bool, service: &str) -> Self {
+        let mut peer_template = HttpPeer::new("0.0.0.0:1", tls, host.to_string());
+        // gRPC needs HTTP/2, which plaintext peers speak with prior knowledge (h2c)
+        peer_template.options.set_http_version(2, 2);
+
+        let mut req = RequestHeader::build("POST", HEALTH_CHECK_PATH.as_bytes(), None)
+            .expect("health check request is valid");
+        req.insert_header(header::CONTENT_TYPE, "application/grpc")
+            .expect("content type is valid");
+        req.insert_header(header::TE, "trailers")
+            .expect("te is valid");
+        if !host.is_empty() {
+            req.insert_header(header::HOST, host)
+                .expect("host is valid");
+        }
+
+        Self {
+            consecutive_success: 1,
+            consecutive_failure: 1,
+            peer_template,
+            port_override: None,
+            req,
+            body: encode_request(service),
+            connector: Connector::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheckTrait for GrpcHealthCheck {
+    fn health_threshold(&self, success: bool) -> usize {
+        if success {
+            self.consecutive_success
+        } else {
+            self.consecutive_failure
+        }
+    }
+
+    async fn check(&self, target: &Backend) -> Result<()> {
+        let mut peer = self.peer_template.clone();
+        peer._address = target.addr.clone();
+        if let Some(port) = self.port_override {
+            peer._address.set_port(port);
+        }
+
+        let mut req = Box::new(self.req.clone());
+        if req.headers.get(header::HOST).is_none() {
+            req.insert_header(header::HOST, node_host(&peer._address))?;
+        }
+
+        let (mut session, _) = self.connector.get_http_session(&peer).await?;
+        session.set_read_timeout(peer.options.read_timeout);
+        let HttpSession::H2(h2) = &mut session else {
+            return Error::e_explain(
+                CHECK_FAILED,
+                "gRPC health check: node does not speak HTTP/2",
+            );
+        };
+
+        h2.write_request_header(req, false)?;
+        h2.write_request_body(self.body.clone(), true).await?;
+        h2.read_response_header().await?;
+
+        let resp = h2.response_header().expect("just read");
+        if resp.status != 200 {
+            return Error::e_explain(
+                ErrorType::CustomCode("non 200 code", resp.status.as_u16()),
+                "during gRPC health check",
+            );
+        }
+        // Errors come as a trailers-only response
+        let mut grpc_status = resp.headers.get("grpc-status").cloned();
+
+        let mut body = BytesMut::new();
+        while let Some(chunk) = h2.read_response_body().await? {
+            if body.len() + chunk.len() > MAX_RESPONSE_SIZE {
+                return Error::e_explain(CHECK_FAILED, "gRPC health check response too large");
+            }
+            body.extend_from_slice(&chunk);
+        }
+        if let Some(trailers) = h2.read_trailers().await? {
+            grpc_status = trailers.get("grpc-status").cloned().or(grpc_status);
+        }
+
+        match grpc_status.as_ref().map(|status| status.as_bytes()) {
+            Some(b"0") => {}
+            Some(status) => {
+                return Error::e_explain(
+                    CHECK_FAILED,
+                    format!(
+                        "gRPC health check failed with grpc-status {}",
+                        String::from_utf8_lossy(status)
+                    ),
+                )
+            }
+            None => {
+                return Error::e_explain(
+                    CHECK_FAILED,
+                    "gRPC health check response without grpc-status",
+                )
+            }
+        }
+
+        match decode_serving_status(&body) {
+            Some(SERVING) => Ok(()),
+            Some(status) => Error::e_explain(
+                CHECK_FAILED,
+                format!("gRPC health check status {status}, expected SERVING"),
+            ),
+            None => Error::e_explain(CHECK_FAILED, "malformed gRPC health check response"),
+        }
+    }
+}
+
+/// Encodes a length-prefixed `HealthCheckRequest` message.
+fn encode_request(service: &str) -> Bytes {
+    let mut message = BytesMut::new();
+    if !service.is_empty() {
+        // Field 1 (`service`), length delimited
+        message.put_u8(0x0a);
+        put_varint(&mut message, service.len() as u64);
+        message.put_slice(service.as_bytes());
+    }
+
+    let mut frame = BytesMut::with_capacity(5 + message.len());
+    frame.put_u8(0); // uncompressed
+    frame.put_u32(message.len() as u32);
+    frame.put_slice(&message);
+    frame.freeze()
+}
+
+/// Reads `status` from a length-prefixed `HealthCheckResponse` message. An absent field is
+/// `UNKNOWN` (0), as in protobuf.
+fn decode_serving_status(frame: &[u8]) -> Option<u64> {
+    let (&compressed, rest) = frame.split_first()?;
+    if compressed != 0 || rest.len() < 4 {
+        return None;
+    }
+    let (len, mut message) = rest.split_at(4);
+    let len = u32::from_be_bytes(len.try_into().ok()?) as usize;
+    message = message.get(..len)?;
+
+    let mut status = 0;
+    while !message.is_empty() {
+        let key = get_varint(&mut message)?;
+        match (key >> 3, key & 0x7) {
+            (1, 0) => status = get_varint(&mut message)?,
+            // Skip unknown fields
+            (_, 0) => {
+                get_varint(&mut message)?;
+            }
+            (_, 2) => {
+                let len = get_varint(&mut message)? as usize;
+                message = message.get(len..)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(status)
+}
+
+fn put_varint(buf: &mut BytesMut, mut value: u64) {
+    while value >= 0x80 {
+        buf.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+fn get_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grpc_health_messages() {
+        assert_eq!(&encode_request("")[..], &[0, 0, 0, 0, 0]);
+        assert_eq!(
+            &encode_request("svc")[..],
+            &[0, 0, 0, 0, 5, 0x0a, 3, b's', b'v', b'c']
+        );
+
+        assert_eq!(
+            decode_serving_status(&[0, 0, 0, 0, 2, 0x08, 1]),
+            Some(SERVING)
+        );
+        // NOT_SERVING, after an unknown string field
+        assert_eq!(
+            decode_serving_status(&[0, 0, 0, 0, 5, 0x12, 1, b'x', 0x08, 2]),
+            Some(2)
+        );
+        assert_eq!(decode_serving_status(&[0, 0, 0, 0, 0]), Some(0));
+        assert_eq!(decode_serving_status(&[0, 0, 0, 0, 2, 0x08]), None);
+        assert_eq!(decode_serving_status(&[1, 0, 0, 0, 0]), None);
+    }
+}
@@ -1,14 +1,176 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use async_trait::async_trait;
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
 use pingora_core::{
+    protocols::l4::socket::SocketAddr,
     server::ShutdownWatch,
     services::{background::BackgroundService, Service},
 };
+use pingora_load_balancing::{
+    discovery::ServiceDiscovery, health_check::HealthCheck as HealthCheckTrait, Backend,
+};
+use serde::Serialize;
 use tokio::sync::{broadcast, watch};
 
+/// Health of an upstream node as seen by the load balancer.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeHealth {
+    pub address: String,
+    pub weight: usize,
+    pub priority: i32,
+    /// Whether the active health check considers the node healthy.
+    pub healthy: bool,
+    /// Whether the passive health check ejected the node.
+    pub ejected: bool,
+    pub last_check: Option<CheckResult>,
+    pub consecutive_failures: u32,
+    pub consecutive_successes: u32,
+    /// When the node last turned healthy or unhealthy, in seconds since the Unix epoch.
+    pub last_transition: Option<u64>,
+}
+
+/// Result of the latest active probe of a node.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+}
+
+/// Upstream load balancer driven by the health check service.
+pub trait UpstreamHealth: BackgroundService + Send + Sync {
+    /// Health of every node currently known to the load balancer.
+    fn nodes_health(&self) -> Vec<NodeHealth>;
+}
+
+/// Active probe history of a single node.
+#[derive(Debug, Clone, Default)]
+pub struct NodeCheckRecord {
+    pub last_check: Option<CheckResult>,
+    pub consecutive_failures: u32,
+    pub consecutive_successes: u32,
+    pub last_transition: Option<u64>,
+}
+
+/// Active probe history of the nodes of a priority tier.
+#[derive(Default)]
+pub struct HealthRecords {
+    nodes: DashMap<SocketAddr, NodeCheckRecord>,
+}
+
+impl HealthRecords {
+    pub fn get(&self, addr: &SocketAddr) -> Option<NodeCheckRecord> {
+        self.nodes.get(addr).map(|record| record.clone())
+    }
+
+    fn observe_check(&self, addr: &SocketAddr, result: &pingora_error::Result<()>) {
+        let mut record = self.nodes.entry(addr.clone()).or_default();
+        match result {
+            Ok(()) => {
+                record.consecutive_successes += 1;
+                record.consecutive_failures = 0;
+            }
+            Err(_) => {
+                record.consecutive_failures += 1;
+                record.consecutive_successes = 0;
+            }
+        }
+        record.last_check = Some(CheckResult {
+            success: result.is_ok(),
+            error: result
+                .as_ref()
+                .err()
+                .map(|e| e.to_string().trim().to_string()),
+            time: unix_now(),
+        });
+    }
+
+    fn observe_transition(&self, addr: &SocketAddr) {
+        self.nodes.entry(addr.clone()).or_default().last_transition = Some(unix_now());
+    }
+
+    /// Forgets nodes the tier no longer has.
+    fn observe_nodes(&self, backends: &BTreeSet<Backend>) {
+        let addrs: HashSet<&SocketAddr> = backends.iter().map(|backend| &backend.addr).collect();
+        self.nodes.retain(|addr, _| addrs.contains(addr));
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Active health check that records every probe result for the status server.
+pub struct RecordingHealthCheck {
+    inner: Box<dyn HealthCheckTrait + Send + Sync + 'static>,
+    records: Arc<HealthRecords>,
+}
+
+impl RecordingHealthCheck {
+    pub fn new(
+        inner: Box<dyn HealthCheckTrait + Send + Sync + 'static>,
+        records: Arc<HealthRecords>,
+    ) -> Self {
+        Self { inner, records }
+    }
+}
+
+#[async_trait]
+impl HealthCheckTrait for RecordingHealthCheck {
+    async fn check(&self, target: &Backend) -> pingora_error::Result<()> {
+        let result = self.inner.check(target).await;
+        self.records.observe_check(&target.addr, &result);
+        result
+    }
+
+    async fn health_status_change(&self, target: &Backend, healthy: bool) {
+        self.records.observe_transition(&target.addr);
+        self.inner.health_status_change(target, healthy).await;
+    }
+
+    fn backend_summary(&self, target: &Backend) -> String {
+        self.inner.backend_summary(target)
+    }
+
+    fn health_threshold(&self, success: bool) -> usize {
+        self.inner.health_threshold(success)
+    }
+}
+
+/// Discovery that drops the probe history of nodes leaving the tier.
+pub struct RecordingDiscovery {
+    inner: Box<dyn ServiceDiscovery + Send + Sync>,
+    records: Arc<HealthRecords>,
+}
+
+impl RecordingDiscovery {
+    pub fn new(
+        inner: Box<dyn ServiceDiscovery + Send + Sync>,
+        records: Arc<HealthRecords>,
+    ) -> Self {
+        Self { inner, records }
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for RecordingDiscovery {
+    async fn discover(&self) -> pingora_error::Result<(BTreeSet<Backend>, HashMap<u64, bool>)> {
+        let discovered = self.inner.discover().await?;
+        self.records.observe_nodes(&discovered.0);
+        Ok(discovered)
+    }
+}
+
 /// Registry update event types
 #[derive(Debug, Clone)]
 pub enum RegistryUpdate {
@@ -18,7 +180,7 @@ pub enum RegistryUpdate {
 
 /// Registered upstream information
 struct RegisteredUpstream {
-    load_balancer: Arc<dyn BackgroundService + Send + Sync>,
+    load_balancer: Arc<dyn UpstreamHealth>,
     shutdown_tx: watch::Sender<bool>,
     shutdown_rx: watch::Receiver<bool>,
 }
@@ -52,7 +214,7 @@ impl HealthCheckRegistry {
     pub fn register_upstream(
         &self,
         upstream_id: String,
-        load_balancer: Arc<dyn BackgroundService + Send + Sync>,
+        load_balancer: Arc<dyn UpstreamHealth>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
@@ -124,11 +286,7 @@ impl HealthCheckRegistry {
     pub fn get_upstream_for_start(
         &self,
         upstream_id: &str,
-    ) -> Option<(
-        String,
-        Arc<dyn BackgroundService + Send + Sync>,
-        watch::Receiver<bool>,
-    )> {
+    ) -> Option<(String, Arc<dyn UpstreamHealth>, watch::Receiver<bool>)> {
         self.upstreams.get(upstream_id).map(|registered| {
             (
                 upstream_id.to_string(),
@@ -145,6 +303,21 @@ impl HealthCheckRegistry {
             .map(|entry| entry.key().clone())
             .collect()
     }
+
+    /// Node health of every registered upstream, keyed by upstream ID.
+    pub fn upstreams_health(&self) -> Vec<(String, Vec<NodeHealth>)> {
+        self.upstreams
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.load_balancer.nodes_health()))
+            .collect()
+    }
+
+    /// Node health of a registered upstream.
+    pub fn upstream_health(&self, upstream_id: &str) -> Option<Vec<NodeHealth>> {
+        self.upstreams
+            .get(upstream_id)
+            .map(|registered| registered.load_balancer.nodes_health())
+    }
 }
 
 /// Health check executor that manages running health check tasks.
@@ -372,7 +545,7 @@ impl SharedHealthCheckService {
     pub fn register_upstream(
         &self,
         upstream_id: String,
-        load_balancer: Arc<dyn BackgroundService + Send + Sync>,
+        load_balancer: Arc<dyn UpstreamHealth>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.registry.register_upstream(upstream_id, load_balancer)
     }
@@ -381,6 +554,16 @@ impl SharedHealthCheckService {
     pub fn unregister_upstream(&self, upstream_id: &str) -> bool {
         self.registry.unregister_upstream(upstream_id)
     }
+
+    /// Node health of every registered upstream, keyed by upstream ID.
+    pub fn upstreams_health(&self) -> Vec<(String, Vec<NodeHealth>)> {
+        self.registry.upstreams_health()
+    }
+
+    /// Node health of a registered upstream.
+    pub fn upstream_health(&self, upstream_id: &str) -> Option<Vec<NodeHealth>> {
+        self.registry.upstream_health(upstream_id)
+    }
 }
 
 #[async_trait]
@@ -406,3 +589,73 @@ impl Service for SharedHealthCheckService {
 /// Global shared health check service instance
 pub static SHARED_HEALTH_CHECK_SERVICE: Lazy<SharedHealthCheckService> =
     Lazy::new(SharedHealthCheckService::new);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use pingora_error::{Error, ErrorType};
+    use pingora_load_balancing::discovery::Static;
+
+    use super::*;
+
+    /// Probe whose outcome the test switches.
+    struct SwitchedCheck(Arc<AtomicBool>);
+
+    #[async_trait]
+    impl HealthCheckTrait for SwitchedCheck {
+        async fn check(&self, _target: &Backend) -> pingora_error::Result<()> {
+            if self.0.load(Ordering::Relaxed) {
+                Ok(())
+            } else {
+                Error::e_explain(ErrorType::ConnectRefused, "probe failed")
+            }
+        }
+
+        fn health_threshold(&self, _success: bool) -> usize {
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recording_health_check() {
+        let up = Arc::new(AtomicBool::new(false));
+        let records = Arc::new(HealthRecords::default());
+        let check = RecordingHealthCheck::new(Box::new(SwitchedCheck(up.clone())), records.clone());
+        let backend = Backend::new("127.0.0.1:8080").unwrap();
+
+        assert!(records.get(&backend.addr).is_none());
+        assert!(check.check(&backend).await.is_err());
+        assert!(check.check(&backend).await.is_err());
+        let record = records.get(&backend.addr).unwrap();
+        assert_eq!(record.consecutive_failures, 2);
+        let last_check = record.last_check.unwrap();
+        assert!(!last_check.success);
+        assert!(last_check.error.unwrap().contains("probe failed"));
+        assert!(record.last_transition.is_none());
+
+        up.store(true, Ordering::Relaxed);
+        check.check(&backend).await.unwrap();
+        check.health_status_change(&backend, true).await;
+        let record = records.get(&backend.addr).unwrap();
+        assert_eq!(record.consecutive_failures, 0);
+        assert_eq!(record.consecutive_successes, 1);
+        assert!(record.last_check.unwrap().success);
+        assert!(record.last_transition.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_recording_discovery() {
+        let records = Arc::new(HealthRecords::default());
+        let kept = Backend::new("127.0.0.1:8080").unwrap();
+        let removed = Backend::new("127.0.0.1:8081").unwrap();
+        records.observe_check(&kept.addr, &Ok(()));
+        records.observe_check(&removed.addr, &Ok(()));
+
+        let discovery =
+            RecordingDiscovery::new(Static::new(BTreeSet::from([kept.clone()])), records.clone());
+        discovery.discover().await.unwrap();
+        assert!(records.get(&kept.addr).is_some());
+        assert!(records.get(&removed.addr).is_none());
+    }
+}
@@ -0,0 +1,200 @@
+use async_trait::async_trait;
+use bytes::BytesMut;
+use http::{header, HeaderName};
+use pingora_core::{
+    connectors::http::Connector, protocols::l4::socket::SocketAddr, upstreams::peer::HttpPeer,
+};
+use pingora_error::{Error, ErrorType, Result};
+use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_load_balancing::{health_check::HealthCheck as HealthCheckTrait, Backend};
+use regex::bytes::Regex;
+
+/// Largest part of a response body matched against `expected_body`.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+/// Error of probes the node answered but failed.
+const CHECK_FAILED: ErrorType = ErrorType::Custom("HttpHealthCheckFailed");
+
+/// Match on a health check response body.
+pub enum BodyMatcher {
+    Contains(Vec<u8>),
+    Regex(Regex),
+}
+
+impl BodyMatcher {
+    fn matches(&self, body: &[u8]) -> bool {
+        match self {
+            BodyMatcher::Contains(text) => {
+                text.is_empty() || body.windows(text.len()).any(|window| window == text)
+            }
+            BodyMatcher::Regex(regex) => regex.is_match(body),
+        }
+    }
+}
+
+/// Active health check sending an HTTP request to the node.
+///
+/// A node is healthy when it answers with one of `http_statuses`, carries the
+/// `expected_headers` and its body matches `expected_body`.
+pub struct HttpHealthCheck {
+    /// Number of successful checks to flip from unhealthy to healthy.
+    pub consecutive_success: usize,
+    /// Number of failed checks to flip from healthy to unhealthy.
+    pub consecutive_failure: usize,
+    /// How to connect to the node; its address is replaced by the node's.
+    pub peer_template: HttpPeer,
+    /// Port to check instead of the node's.
+    pub port_override: Option<u16>,
+    /// The request sent to the node; without a `Host` header the node's address is the host.
+    pub req: RequestHeader,
+    /// Statuses of healthy responses; empty accepts `200` only.
+    pub http_statuses: Vec<u32>,
+    /// Response headers required to contain the given value.
+    pub expected_headers: Vec<(HeaderName, String)>,
+    pub expected_body: Option<BodyMatcher>,
+    connector: Connector,
+}
+
+impl HttpHealthCheck {
+    pub fn new(host: &str, tls: bool) -> Self {
+        let mut req =
+            RequestHeader::build("GET", b"/", None).expect("health check request is valid");
+        if !host.is_empty() {
+            req.insert_header(header::HOST, host)
+                .expect("host is valid");
+        }
+
+        Self {
+            consecutive_success: 1,
+            consecutive_failure: 1,
+            peer_template: HttpPeer::new("0.0.0.0:1", tls, host.to_string()),
+            port_override: None,
+            req,
+            http_statuses: Vec::new(),
+            expected_headers: Vec::new(),
+            expected_body: None,
+            connector: Connector::new(None),
+        }
+    }
+
+    fn validate_header(&self, resp: &ResponseHeader) -> Result<()> {
+        let status = resp.status.as_u16();
+        let healthy = if self.http_statuses.is_empty() {
+            status == 200
+        } else {
+            self.http_statuses.contains(&(status as u32))
+        };
+        if !healthy {
+            return Error::e_explain(
+                ErrorType::CustomCode("unexpected status", status),
+                "during http health check",
+            );
+        }
+
+        for (name, expected) in &self.expected_headers {
+            let found = resp.headers.get_all(name).iter().any(|value| {
+                value
+                    .to_str()
+                    .is_ok_and(|value| value.contains(expected.as_str()))
+            });
+            if !found {
+                return Error::e_explain(
+                    CHECK_FAILED,
+                    format!("response header {name} does not contain {expected:?}"),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Host of checks sent without a `Host` header: the node's address, or `localhost` for Unix
+/// domain sockets, whose path is no valid host.
+pub(super) fn node_host(addr: &SocketAddr) -> String {
+    match addr {
+        SocketAddr::Inet(addr) => addr.to_string(),
+        SocketAddr::Unix(_) => "localhost".to_string(),
+    }
+}
+
+#[async_trait]
+impl HealthCheckTrait for HttpHealthCheck {
+    fn health_threshold(&self, success: bool) -> usize {
+        if success {
+            self.consecutive_success
+        } else {
+            self.consecutive_failure
+        }
+    }
+
+    async fn check(&self, target: &Backend) -> Result<()> {
+        let mut peer = self.peer_template.clone();
+        peer._address = target.addr.clone();
+        if let Some(port) = self.port_override {
+            peer._address.set_port(port);
+        }
+
+        let mut req = Box::new(self.req.clone());
+        if req.headers.get(header::HOST).is_none() {
+            req.insert_header(header::HOST, node_host(&peer._address))?;
+        }
+
+        let (mut session, _) = self.connector.get_http_session(&peer).await?;
+        session.write_request_header(req).await?;
+        session.finish_request_body().await?;
+        session.set_read_timeout(peer.options.read_timeout);
+
+        session.read_response_header().await?;
+        self.validate_header(session.response_header().expect("just read"))?;
+
+        let Some(expected_body) = &self.expected_body else {
+            return Ok(());
+        };
+        let mut body = BytesMut::new();
+        while body.len() < MAX_BODY_SIZE {
+            match session.read_response_body().await? {
+                Some(chunk) => body.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        body.truncate(MAX_BODY_SIZE);
+
+        if !expected_body.matches(&body) {
+            return Error::e_explain(CHECK_FAILED, "response body does not match expected_body");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_check_validation() {
+        let mut check = HttpHealthCheck::new("example.com", false);
+        check.expected_headers = vec![(header::CONTENT_TYPE, "json".to_string())];
+
+        let mut resp = ResponseHeader::build(200, None).unwrap();
+        resp.insert_header(header::CONTENT_TYPE, "application/json")
+            .unwrap();
+        assert!(check.validate_header(&resp).is_ok());
+
+        resp.insert_header(header::CONTENT_TYPE, "text/plain")
+            .unwrap();
+        assert!(check.validate_header(&resp).is_err());
+
+        check.expected_headers.clear();
+        let resp = ResponseHeader::build(204, None).unwrap();
+        assert!(check.validate_header(&resp).is_err());
+        check.http_statuses = vec![200, 204];
+        assert!(check.validate_header(&resp).is_ok());
+
+        let body = br#"{"status":"degraded"}"#;
+        assert!(!BodyMatcher::Contains(br#""status":"ok""#.to_vec()).matches(body));
+        assert!(BodyMatcher::Contains(b"degraded".to_vec()).matches(body));
+        let regex = Regex::new(r#""status":\s*"(ok|up)""#).unwrap();
+        assert!(!BodyMatcher::Regex(regex.clone()).matches(body));
+        assert!(BodyMatcher::Regex(regex).matches(br#"{"status": "up"}"#));
+    }
+}
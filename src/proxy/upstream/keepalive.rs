@@ -0,0 +1,133 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::core::PooledConnection;
+
+/// Bounds the idle HTTP/1.1 connections an upstream keeps to its nodes.
+///
+/// Pingora keeps a single connection pool for every upstream, so each upstream counts its own
+/// connections by file descriptor: those in use that may return to the pool, and those
+/// returned within the idle timeout. A connection that would exceed the size is closed after
+/// its request. Idle connections closed early by the node are still counted until their idle
+/// timeout, which errs on keeping fewer.
+pub struct KeepalivePool {
+    size: usize,
+    idle_timeout: Duration,
+    connections: Mutex<PoolConnections>,
+}
+
+#[derive(Default)]
+struct PoolConnections {
+    /// Connections in use that go back to the pool after their request.
+    active: HashSet<u64>,
+    /// Connections in the pool and when they went back.
+    idle: HashMap<u64, Instant>,
+}
+
+impl KeepalivePool {
+    pub fn new(size: usize, idle_timeout: Duration) -> Self {
+        Self {
+            size,
+            idle_timeout,
+            connections: Mutex::new(PoolConnections::default()),
+        }
+    }
+
+    /// Counts a connection taken for a request, reused from the pool or new, against the pool
+    /// until the returned slot is dropped.
+    pub fn acquire(self: &Arc<Self>, connection: u64) -> PoolSlot {
+        let now = Instant::now();
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        // Reused connections leave the pool, and a new connection may get the descriptor of a
+        // closed idle one
+        connections.idle.remove(&connection);
+        connections
+            .idle
+            .retain(|_, released| now.duration_since(*released) < self.idle_timeout);
+
+        let keepalive = connections.active.len() + connections.idle.len() < self.size;
+        if keepalive {
+            connections.active.insert(connection);
+        }
+        PoolSlot {
+            pool: keepalive.then(|| self.clone()),
+            connection,
+            released: false,
+        }
+    }
+}
+
+/// A connection counted against a [`KeepalivePool`].
+pub struct PoolSlot {
+    /// The pool the connection returns to, `None` when it is closed after the request.
+    pool: Option<Arc<KeepalivePool>>,
+    connection: u64,
+    released: bool,
+}
+
+impl PooledConnection for PoolSlot {
+    fn keepalive(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    fn release(&mut self) {
+        self.released = true;
+    }
+}
+
+impl Drop for PoolSlot {
+    fn drop(&mut self) {
+        let Some(pool) = &self.pool else {
+            return;
+        };
+        let mut connections = pool.connections.lock().unwrap_or_else(|e| e.into_inner());
+        connections.active.remove(&self.connection);
+        if self.released {
+            connections.idle.insert(self.connection, Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keepalive_pool_size() {
+        let pool = Arc::new(KeepalivePool::new(2, Duration::from_secs(60)));
+
+        let mut first = pool.acquire(1);
+        let mut second = pool.acquire(2);
+        let third = pool.acquire(3);
+        assert!(first.keepalive() && second.keepalive());
+        assert!(!third.keepalive());
+        drop(third);
+
+        // Released connections stay counted while idle in the pool
+        first.release();
+        second.release();
+        drop((first, second));
+        assert!(!pool.acquire(3).keepalive());
+
+        // Reusing an idle connection takes it out of the pool, and a failed request does not
+        // put it back
+        let reused = pool.acquire(1);
+        assert!(reused.keepalive());
+        drop(reused);
+        assert!(pool.acquire(4).keepalive());
+    }
+
+    #[test]
+    fn test_keepalive_pool_idle_timeout() {
+        let pool = Arc::new(KeepalivePool::new(1, Duration::ZERO));
+        let mut slot = pool.acquire(1);
+        slot.release();
+        drop(slot);
+
+        // Idle connections past the timeout are gone from the pool
+        assert!(pool.acquire(2).keepalive());
+    }
+}
@@ -1,30 +1,50 @@
 use std::{sync::Arc, time::Duration};
 
+use async_trait::async_trait;
 use dashmap::DashMap;
-use http::Uri;
+use futures::future::join_all;
+use http::{HeaderName, Uri};
 use log::info;
 use once_cell::sync::Lazy;
-use pingora::services::background::background_service;
-use pingora_core::upstreams::peer::HttpPeer;
-use pingora_error::Error;
-use pingora_http::{RequestHeader, ResponseHeader};
+use pingora_core::{
+    protocols::l4::socket::SocketAddr, server::ShutdownWatch,
+    services::background::BackgroundService, upstreams::peer::HttpPeer,
+};
+use pingora_http::RequestHeader;
 use pingora_load_balancing::{
-    health_check::{HealthCheck as HealthCheckTrait, HttpHealthCheck, TcpHealthCheck},
+    discovery::ServiceDiscovery,
+    health_check::{HealthCheck as HealthCheckTrait, TcpHealthCheck},
     selection::{
         consistent::KetamaHashing, BackendIter, BackendSelection, FVNHash, Random, RoundRobin,
     },
     Backend, Backends, LoadBalancer,
 };
 use pingora_proxy::Session;
+use regex::bytes::Regex;
 
 use crate::{
     config::{self, Identifiable},
-    core::{ErrorContext, ProxyError, ProxyResult, UpstreamSelector},
+    core::{
+        ErrorContext, InflightRequest, PooledConnection, ProxyError, ProxyResult, UpstreamOutcome,
+        UpstreamSelector,
+    },
     proxy::MapOperations,
     utils::request::request_selector_key,
 };
 
-use super::{discovery::HybridDiscovery, health_check::SHARED_HEALTH_CHECK_SERVICE};
+use super::{
+    discovery::{HybridDiscovery, NodePriority},
+    grpc_check::GrpcHealthCheck,
+    health_check::{
+        HealthRecords, NodeHealth, RecordingDiscovery, RecordingHealthCheck, UpstreamHealth,
+        SHARED_HEALTH_CHECK_SERVICE,
+    },
+    http_check::{BodyMatcher, HttpHealthCheck},
+    keepalive::KeepalivePool,
+    passive_check::{PassiveAwareHealthCheck, PassiveHealthCheck},
+    selection::{EwmaLatency, LeastConnection, NodeLoad},
+    slow_start::{SlowStart, SlowStartDiscovery, SlowStartHealthCheck},
+};
 
 /// Runs a closure over the inner LB for any SelectionLB variant, eliminating repetitive match arms.
 macro_rules! with_lb {
@@ -34,6 +54,8 @@ macro_rules! with_lb {
             SelectionLB::Random($lb_var) => $body,
             SelectionLB::Fnv($lb_var) => $body,
             SelectionLB::Ketama($lb_var) => $body,
+            SelectionLB::LeastConn($lb_var) => $body,
+            SelectionLB::Ewma($lb_var) => $body,
         }
     };
 }
@@ -55,6 +77,8 @@ pub fn upstream_fetch(id: &str) -> Option<Arc<ProxyUpstream>> {
 pub struct ProxyUpstream {
     pub inner: config::Upstream,
     lb: SelectionLB,
+    /// Bound of the idle connections kept to the nodes, when `keepalive_pool.size` is set.
+    keepalive: Option<Arc<KeepalivePool>>,
 }
 
 impl Identifiable for ProxyUpstream {
@@ -80,9 +104,17 @@ impl ProxyUpstream {
             ProxyError::Configuration(format!("Failed to create load balancer: {e}"))
         })?;
 
+        let keepalive = upstream.keepalive_pool.as_ref().and_then(|pool| {
+            let size = pool.size?;
+            Some(Arc::new(KeepalivePool::new(
+                size,
+                Duration::from_secs(pool.idle_timeout),
+            )))
+        });
         let mut proxy_upstream = ProxyUpstream {
             inner: upstream,
             lb,
+            keepalive,
         };
 
         // Register with shared health check service
@@ -98,9 +130,7 @@ impl ProxyUpstream {
 
     /// Register health check with shared service
     fn register_health_check(&mut self) -> ProxyResult<()> {
-        let load_balancer: Arc<
-            dyn pingora_core::services::background::BackgroundService + Send + Sync,
-        > = with_lb!(&self.lb, |lb| lb.upstreams.clone());
+        let load_balancer: Arc<dyn UpstreamHealth> = with_lb!(&self.lb, |lb| lb.upstreams.clone());
 
         let upstream_id = self.inner.id.clone();
 
@@ -119,6 +149,24 @@ impl ProxyUpstream {
         Ok(())
     }
 
+    /// Health of the upstream's nodes, for the status server.
+    pub fn nodes_health(&self) -> Vec<NodeHealth> {
+        with_lb!(&self.lb, |lb| lb.upstreams.nodes_health())
+    }
+
+    /// Selects a healthy backend for a non-HTTP connection, hashing on `key` and avoiding
+    /// the `excluded` nodes when another one is healthy.
+    pub fn select_backend_by_key(&self, key: &[u8], excluded: &[SocketAddr]) -> Option<Backend> {
+        with_lb!(&self.lb, |lb| lb.select(key, excluded))
+    }
+
+    /// Feeds the result of talking to the node at `addr` to the passive health check.
+    pub fn report_node_outcome(&self, addr: &SocketAddr, outcome: UpstreamOutcome) {
+        if let Some(passive) = with_lb!(&self.lb, |lb| lb.upstreams.passive.as_ref()) {
+            passive.report(addr, outcome);
+        }
+    }
+
     /// Stop health check service for this upstream
     fn stop_health_check(&mut self) {
         let upstream_id = self.id();
@@ -151,11 +199,15 @@ impl Drop for ProxyUpstream {
 
 // Implementation of UpstreamSelector trait for decoupling from core module
 impl UpstreamSelector for ProxyUpstream {
-    fn select_backend<'a>(&'a self, session: &'a mut Session) -> Option<Backend> {
+    fn select_backend<'a>(
+        &'a self,
+        session: &'a mut Session,
+        excluded: &[SocketAddr],
+    ) -> Option<Backend> {
         let key = request_selector_key(session, &self.inner.hash_on, self.inner.key.as_str());
         log::debug!("proxy lb key: {}", &key);
 
-        let mut backend = with_lb!(&self.lb, |lb| lb.upstreams.select(key.as_bytes(), 256));
+        let mut backend = with_lb!(&self.lb, |lb| lb.select(key.as_bytes(), excluded));
 
         if let Some(backend) = backend.as_mut() {
             if let Some(peer) = backend.ext.get_mut::<HttpPeer>() {
@@ -174,6 +226,10 @@ impl UpstreamSelector for ProxyUpstream {
         self.inner.retry_timeout
     }
 
+    fn get_retry_on(&self) -> Option<&config::RetryOn> {
+        self.inner.retry_on.as_ref()
+    }
+
     fn get_pass_host(&self) -> &config::UpstreamPassHost {
         &self.inner.pass_host
     }
@@ -187,6 +243,21 @@ impl UpstreamSelector for ProxyUpstream {
             }
         }
     }
+
+    fn report_outcome(&self, peer: &HttpPeer, outcome: UpstreamOutcome) {
+        self.report_node_outcome(&peer._address, outcome);
+    }
+
+    fn start_request(&self, peer: &HttpPeer) -> Option<Box<dyn InflightRequest>> {
+        with_lb!(&self.lb, |lb| lb.load.as_ref())
+            .map(|load| Box::new(load.start_request(&peer._address)) as Box<dyn InflightRequest>)
+    }
+
+    fn pool_connection(&self, connection: u64) -> Option<Box<dyn PooledConnection>> {
+        self.keepalive
+            .as_ref()
+            .map(|pool| Box::new(pool.acquire(connection)) as Box<dyn PooledConnection>)
+    }
 }
 
 enum SelectionLB {
@@ -194,6 +265,8 @@ enum SelectionLB {
     Random(LB<Random>),
     Fnv(LB<FVNHash>),
     Ketama(LB<KetamaHashing>),
+    LeastConn(LB<LeastConnection>),
+    Ewma(LB<EwmaLatency>),
 }
 
 impl TryFrom<config::Upstream> for SelectionLB {
@@ -211,50 +284,306 @@ impl TryFrom<config::Upstream> for SelectionLB {
             config::SelectionType::Ketama => {
                 Ok(SelectionLB::Ketama(LB::<KetamaHashing>::try_from(value)?))
             }
+            config::SelectionType::LeastConn => Ok(SelectionLB::LeastConn(
+                LB::<LeastConnection>::new_with_load(value)?,
+            )),
+            config::SelectionType::Ewma => {
+                Ok(SelectionLB::Ewma(LB::<EwmaLatency>::new_with_load(value)?))
+            }
+        }
+    }
+}
+
+/// Load balancers of an upstream's priority tiers, highest priority first.
+///
+/// Runs discovery and health checks of every tier when driven by the shared health check
+/// service.
+struct PriorityTiers<BS: BackendSelection> {
+    tiers: Vec<LoadBalancer<BS>>,
+    /// Priority of each tier, in the order of `tiers`.
+    priorities: Vec<i32>,
+    passive: Option<Arc<PassiveHealthCheck>>,
+    /// Active probe history of each tier, in the order of `tiers`; empty without health checks.
+    records: Vec<Arc<HealthRecords>>,
+    /// Slow start of each tier, in the order of `tiers`; empty without `slow_start`.
+    slow_starts: Vec<Arc<SlowStart>>,
+}
+
+#[async_trait]
+impl<BS> BackgroundService for PriorityTiers<BS>
+where
+    BS: BackendSelection + Send + Sync + 'static,
+    BS::Iter: BackendIter,
+{
+    async fn start(&self, shutdown: ShutdownWatch) {
+        join_all(
+            self.tiers
+                .iter()
+                .map(|tier| BackgroundService::start(tier, shutdown.clone())),
+        )
+        .await;
+    }
+}
+
+impl<BS> UpstreamHealth for PriorityTiers<BS>
+where
+    BS: BackendSelection + Send + Sync + 'static,
+    BS::Iter: BackendIter,
+{
+    fn nodes_health(&self) -> Vec<NodeHealth> {
+        let mut nodes = Vec::new();
+        for (index, (tier, priority)) in self.tiers.iter().zip(&self.priorities).enumerate() {
+            let backends = tier.backends();
+            for backend in backends.get_backend().iter() {
+                let record = self
+                    .records
+                    .get(index)
+                    .and_then(|records| records.get(&backend.addr))
+                    .unwrap_or_default();
+                nodes.push(NodeHealth {
+                    address: match &backend.addr {
+                        SocketAddr::Inet(addr) => addr.to_string(),
+                        // Like the node key, as a socket path alone reads as a file
+                        SocketAddr::Unix(_) => format!("unix:{}", backend.addr),
+                    },
+                    weight: backend.weight,
+                    priority: backend
+                        .ext
+                        .get::<NodePriority>()
+                        .map_or(*priority, |priority| priority.0),
+                    healthy: backends.ready(backend),
+                    ejected: self
+                        .passive
+                        .as_ref()
+                        .is_some_and(|passive| passive.is_ejected(&backend.addr)),
+                    last_check: record.last_check,
+                    consecutive_failures: record.consecutive_failures,
+                    consecutive_successes: record.consecutive_successes,
+                    last_transition: record.last_transition,
+                });
+            }
         }
+        nodes
     }
 }
 
 struct LB<BS: BackendSelection> {
-    upstreams: Arc<LoadBalancer<BS>>,
+    upstreams: Arc<PriorityTiers<BS>>,
+    /// Per-node load fed by proxied requests, for load-aware selections.
+    load: Option<Arc<NodeLoad>>,
+    /// Whether discovered nodes carry a [`NodePriority`] that splits their tier further.
+    discovered_priorities: bool,
+}
+
+impl<BS> LB<BS>
+where
+    BS: BackendSelection + 'static,
+    BS::Iter: BackendIter,
+{
+    /// Selects a healthy backend from the highest priority tier that has one, skipping nodes
+    /// ejected by the passive health check. Nodes in `excluded` are only selected when no other
+    /// node is available.
+    fn select(&self, key: &[u8], excluded: &[SocketAddr]) -> Option<Backend> {
+        let available = |backend: &Backend, healthy: bool| {
+            healthy
+                && !self
+                    .upstreams
+                    .passive
+                    .as_ref()
+                    .is_some_and(|passive| passive.is_ejected(&backend.addr))
+        };
+
+        if !excluded.is_empty() {
+            let backend = self.select_with(key, |backend, healthy| {
+                available(backend, healthy) && !excluded.contains(&backend.addr)
+            });
+            if backend.is_some() {
+                return backend;
+            }
+        }
+
+        self.select_with(key, available)
+    }
+
+    fn select_with(&self, key: &[u8], accept: impl Fn(&Backend, bool) -> bool) -> Option<Backend> {
+        self.upstreams
+            .tiers
+            .iter()
+            .enumerate()
+            .find_map(|(index, tier)| {
+                let top = if self.discovered_priorities {
+                    let backends = tier.backends();
+                    let top = backends
+                        .get_backend()
+                        .iter()
+                        .filter(|backend| accept(backend, backends.ready(backend)))
+                        .map(node_priority)
+                        .max()?;
+                    Some(top)
+                } else {
+                    None
+                };
+                let accept = |backend: &Backend, healthy: bool| {
+                    accept(backend, healthy) && top.is_none_or(|top| node_priority(backend) == top)
+                };
+
+                // Ramping nodes only take their share, unless no other node in the tier can
+                if let Some(slow_start) = self.upstreams.slow_starts.get(index) {
+                    let backend = tier.select_with(key, 256, |backend, healthy| {
+                        accept(backend, healthy) && slow_start.admit(&backend.addr)
+                    });
+                    if backend.is_some() {
+                        return backend;
+                    }
+                }
+                tier.select_with(key, 256, accept)
+            })
+    }
 }
 
 impl<BS> TryFrom<config::Upstream> for LB<BS>
 where
     BS: BackendSelection + Send + Sync + 'static,
     BS::Iter: BackendIter,
+    BS::Config: Clone,
 {
     type Error = ProxyError;
 
     fn try_from(upstream: config::Upstream) -> ProxyResult<Self> {
-        let discovery: HybridDiscovery = upstream.clone().try_into()?;
-        let mut upstreams = LoadBalancer::<BS>::from_backends(Backends::new(Box::new(discovery)));
+        Self::new_with_config(upstream, None)
+    }
+}
 
-        if let Some(check) = upstream.checks {
-            let health_check: Box<dyn HealthCheckTrait + Send + Sync + 'static> =
-                check.clone().into();
-            upstreams.set_health_check(health_check);
+impl<BS> LB<BS>
+where
+    BS: BackendSelection<Config = Arc<NodeLoad>> + Send + Sync + 'static,
+    BS::Iter: BackendIter,
+{
+    /// Creates a load balancer whose selection reads per-node load.
+    fn new_with_load(upstream: config::Upstream) -> ProxyResult<Self> {
+        let load = Arc::new(NodeLoad::default());
+        let mut lb = Self::new_with_config(upstream, Some(load.clone()))?;
+        lb.load = Some(load);
+        Ok(lb)
+    }
+}
 
-            let health_check_frequency = check
-                .active
-                .healthy
-                .map(|healthy| Duration::from_secs(healthy.interval as _))
-                .unwrap_or(Duration::from_secs(1));
+impl<BS> LB<BS>
+where
+    BS: BackendSelection + Send + Sync + 'static,
+    BS::Iter: BackendIter,
+    BS::Config: Clone,
+{
+    fn new_with_config(
+        upstream: config::Upstream,
+        selection_config: Option<BS::Config>,
+    ) -> ProxyResult<Self> {
+        let passive = upstream
+            .checks
+            .as_ref()
+            .and_then(|check| check.passive.clone())
+            .map(|passive_check| Arc::new(PassiveHealthCheck::new(passive_check)));
+
+        // Discovered nodes form a single tier and carry their own priority
+        let priorities = match upstream.discovery_type {
+            Some(_) => vec![0],
+            None => upstream.nodes.priorities(),
+        };
+        let mut records = Vec::new();
+        let mut slow_starts = Vec::new();
+        let tiers = priorities
+            .iter()
+            .map(|&priority| {
+                let mut discovery: Box<dyn ServiceDiscovery + Send + Sync> =
+                    Box::new(HybridDiscovery::new(&upstream, priority)?);
+                let slow_start = upstream
+                    .slow_start
+                    .map(|secs| Arc::new(SlowStart::new(Duration::from_secs(secs))));
+                if let Some(slow_start) = &slow_start {
+                    discovery = Box::new(SlowStartDiscovery::new(discovery, slow_start.clone()));
+                    slow_starts.push(slow_start.clone());
+                }
+                let tier_records = upstream
+                    .checks
+                    .as_ref()
+                    .map(|_| Arc::new(HealthRecords::default()));
+                if let Some(tier_records) = &tier_records {
+                    // Nodes that leave the tier no longer need their history
+                    discovery = Box::new(RecordingDiscovery::new(discovery, tier_records.clone()));
+                    records.push(tier_records.clone());
+                }
+                let mut tier = LoadBalancer::<BS>::from_backends_with_config(
+                    Backends::new(discovery),
+                    selection_config.clone(),
+                );
+                tier.update_frequency = upstream.discovery_type.map(discovery_update_frequency);
+
+                if let Some(check) = upstream.checks.clone() {
+                    let mut health_check: Box<dyn HealthCheckTrait + Send + Sync + 'static> =
+                        check.clone().into();
+
+                    // Active probes are what bring passively ejected nodes back
+                    if let Some(passive) = &passive {
+                        health_check =
+                            Box::new(PassiveAwareHealthCheck::new(health_check, passive.clone()));
+                    }
+                    if let Some(slow_start) = &slow_start {
+                        health_check = Box::new(SlowStartHealthCheck::new(
+                            health_check,
+                            slow_start.clone(),
+                            passive.clone(),
+                        ));
+                    }
+                    if let Some(records) = &tier_records {
+                        health_check =
+                            Box::new(RecordingHealthCheck::new(health_check, records.clone()));
+                    }
+                    tier.set_health_check(health_check);
 
-            upstreams.health_check_frequency = Some(health_check_frequency);
-        }
+                    let health_check_frequency = check
+                        .active
+                        .healthy
+                        .map(|healthy| Duration::from_secs(healthy.interval as _))
+                        .unwrap_or(Duration::from_secs(1));
 
-        // Extract the Arc<LoadBalancer> via background_service().task().
-        // The wrapper is intentionally dropped — health checks are driven by
-        // SHARED_HEALTH_CHECK_SERVICE, not by Pingora's background service mechanism.
-        let background =
-            background_service(&format!("health check for {}", upstream.id), upstreams);
-        let upstreams = background.task();
+                    tier.health_check_frequency = Some(health_check_frequency);
+                }
+
+                Ok(tier)
+            })
+            .collect::<ProxyResult<Vec<_>>>()?;
+
+        Ok(Self {
+            upstreams: Arc::new(PriorityTiers {
+                tiers,
+                priorities,
+                passive,
+                records,
+                slow_starts,
+            }),
+            load: None,
+            discovered_priorities: upstream.discovery_type.is_some(),
+        })
+    }
+}
 
-        Ok(Self { upstreams })
+/// How often nodes of discovery-based upstreams are refreshed from their registry.
+fn discovery_update_frequency(discovery_type: config::DiscoveryType) -> Duration {
+    match discovery_type {
+        config::DiscoveryType::DnsSrv => Duration::from_secs(10),
+        config::DiscoveryType::Consul => Duration::from_secs(5),
+        // Only re-read when changed, so polling is cheap
+        config::DiscoveryType::File => Duration::from_secs(1),
     }
 }
 
+fn node_priority(backend: &Backend) -> i32 {
+    backend
+        .ext
+        .get::<NodePriority>()
+        .map_or(0, |priority| priority.0)
+}
+
 impl From<config::HealthCheck> for Box<dyn HealthCheckTrait + Send + Sync + 'static> {
     fn from(value: config::HealthCheck) -> Self {
         match value.active.r#type {
@@ -262,6 +591,9 @@ impl From<config::HealthCheck> for Box<dyn HealthCheckTrait + Send + Sync + 'sta
             config::ActiveCheckType::HTTP | config::ActiveCheckType::HTTPS => {
                 Into::<Box<HttpHealthCheck>>::into(value)
             }
+            config::ActiveCheckType::GRPC | config::ActiveCheckType::GRPCS => {
+                Into::<Box<GrpcHealthCheck>>::into(value)
+            }
         }
     }
 }
@@ -290,9 +622,10 @@ impl From<config::HealthCheck> for Box<HttpHealthCheck> {
         let tls = value.active.r#type == config::ActiveCheckType::HTTPS;
         let mut health_check = HttpHealthCheck::new(host.as_str(), tls);
 
-        // Set total connection timeout if provided
-        health_check.peer_template.options.total_connection_timeout =
-            Some(Duration::from_secs(value.active.timeout as _));
+        // Bound connecting and reading the response by the check timeout
+        let timeout = Duration::from_secs(value.active.timeout as _);
+        health_check.peer_template.options.total_connection_timeout = Some(timeout);
+        health_check.peer_template.options.read_timeout = Some(timeout);
 
         // Set certificate verification if TLS is enabled
         health_check.peer_template.options.verify_cert = value.active.https_verify_certificate;
@@ -310,15 +643,7 @@ impl From<config::HealthCheck> for Box<HttpHealthCheck> {
             );
         }
 
-        // Insert headers, ensure they are properly formatted
-        for header in value.active.req_headers.iter() {
-            let mut parts = header.splitn(2, ":");
-            if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-                let key = key.trim().to_string();
-                let value = value.trim().to_string();
-                let _ = health_check.req.insert_header(key, &value);
-            }
-        }
+        insert_req_headers(&mut health_check.req, &value.active.req_headers);
 
         // Handle port override
         if let Some(port) = value.active.port {
@@ -328,20 +653,28 @@ impl From<config::HealthCheck> for Box<HttpHealthCheck> {
         // Set the success conditions
         if let Some(healthy) = value.active.healthy {
             health_check.consecutive_success = healthy.successes as _;
+            health_check.http_statuses = healthy.http_statuses;
+        }
 
-            // Validator for HTTP status codes
-            if !healthy.http_statuses.is_empty() {
-                let http_statuses = healthy.http_statuses.clone(); // Clone to move into closure
-                health_check.validator = Some(Box::new(move |header: &ResponseHeader| {
-                    if http_statuses.contains(&(header.status.as_u16() as _)) {
-                        Ok(())
-                    } else {
-                        Err(Error::new_str("Invalid response"))
-                    }
-                }));
+        for (name, expected) in value.active.expected_headers {
+            match HeaderName::from_bytes(name.as_bytes()) {
+                Ok(name) => health_check.expected_headers.push((name, expected)),
+                Err(e) => log::warn!("Invalid expected header name for health check: {e}"),
             }
         }
 
+        health_check.expected_body = match value.active.expected_body {
+            Some(config::BodyMatch::Contains(text)) => Some(BodyMatcher::Contains(text.into())),
+            Some(config::BodyMatch::Regex { regex }) => match Regex::new(&regex) {
+                Ok(regex) => Some(BodyMatcher::Regex(regex)),
+                Err(e) => {
+                    log::warn!("Invalid expected body regex for health check: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+
         // Set the failure conditions
         if let Some(unhealthy) = value.active.unhealthy {
             health_check.consecutive_failure = unhealthy.http_failures as _;
@@ -352,6 +685,50 @@ impl From<config::HealthCheck> for Box<HttpHealthCheck> {
     }
 }
 
+impl From<config::HealthCheck> for Box<GrpcHealthCheck> {
+    fn from(value: config::HealthCheck) -> Self {
+        let host = value.active.host.unwrap_or_default();
+        let tls = value.active.r#type == config::ActiveCheckType::GRPCS;
+        let mut health_check = GrpcHealthCheck::new(&host, tls, &value.active.grpc_service);
+
+        let timeout = Duration::from_secs(value.active.timeout as _);
+        health_check.peer_template.options.total_connection_timeout = Some(timeout);
+        health_check.peer_template.options.read_timeout = Some(timeout);
+        health_check.peer_template.options.verify_cert = value.active.https_verify_certificate;
+
+        // Sent as gRPC metadata, e.g. for authentication
+        insert_req_headers(&mut health_check.req, &value.active.req_headers);
+
+        if let Some(port) = value.active.port {
+            health_check.port_override = Some(port as _);
+        }
+
+        if let Some(healthy) = value.active.healthy {
+            health_check.consecutive_success = healthy.successes as _;
+        }
+
+        // A probe that fails is an HTTP failure, whether at the connection or the gRPC level
+        if let Some(unhealthy) = value.active.unhealthy {
+            health_check.consecutive_failure = unhealthy.http_failures as _;
+        }
+
+        Box::new(health_check)
+    }
+}
+
+/// Adds the `Name: value` headers configured in `req_headers` to a probe request.
+fn insert_req_headers(req: &mut RequestHeader, headers: &[String]) {
+    for header in headers {
+        let Some((name, value)) = header.split_once(':') else {
+            log::warn!("Invalid health check request header: {header}");
+            continue;
+        };
+        if let Err(e) = req.insert_header(name.trim().to_string(), value.trim()) {
+            log::warn!("Invalid health check request header {header}: {e}");
+        }
+    }
+}
+
 // Define a global upstream map, initialized lazily
 pub static UPSTREAM_MAP: Lazy<DashMap<String, Arc<ProxyUpstream>>> = Lazy::new(DashMap::new);
 
@@ -381,3 +758,100 @@ pub fn load_static_upstreams(config: &config::Config) -> ProxyResult<()> {
     log::info!("Loaded {upstream_count} upstreams");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::upstream::discovery::TestNodesFile;
+
+    async fn round_robin(upstream: &str) -> LB<RoundRobin> {
+        let upstream: config::Upstream = serde_yaml::from_str(upstream).unwrap();
+        let lb = LB::<RoundRobin>::try_from(upstream).unwrap();
+        for tier in &lb.upstreams.tiers {
+            tier.update().await.unwrap();
+        }
+        lb
+    }
+
+    fn set_healthy(lb: &LB<RoundRobin>, addr: &str, healthy: bool) {
+        for tier in &lb.upstreams.tiers {
+            let backends = tier.backends();
+            for backend in backends.get_backend().iter() {
+                if backend.addr.to_string() == addr {
+                    backends.set_enable(backend, healthy);
+                }
+            }
+        }
+    }
+
+    fn selected(lb: &LB<RoundRobin>, excluded: &[SocketAddr]) -> String {
+        lb.select(b"", excluded).unwrap().addr.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_select_priority_failover() {
+        let lb = round_robin(
+            "nodes:\n  - {host: 127.0.0.1, port: 8080}\n  - {host: 127.0.0.1, port: 8081, priority: -1}",
+        )
+        .await;
+        assert_eq!(lb.upstreams.priorities, vec![0, -1]);
+        assert_eq!(selected(&lb, &[]), "127.0.0.1:8080");
+
+        set_healthy(&lb, "127.0.0.1:8080", false);
+        assert_eq!(selected(&lb, &[]), "127.0.0.1:8081");
+
+        // Traffic returns to the primary tier once it recovers
+        set_healthy(&lb, "127.0.0.1:8080", true);
+        assert_eq!(selected(&lb, &[]), "127.0.0.1:8080");
+    }
+
+    #[tokio::test]
+    async fn test_select_discovered_priority() {
+        let file = TestNodesFile::new();
+        file.write(
+            r#"[{"host": "127.0.0.1", "port": 8080}, {"host": "127.0.0.1", "port": 8081, "priority": -1}]"#,
+        );
+        let lb = round_robin(&file.upstream_yaml()).await;
+
+        // Discovered nodes share a tier, split by their own priority
+        assert_eq!(lb.upstreams.tiers.len(), 1);
+        assert_eq!(selected(&lb, &[]), "127.0.0.1:8080");
+        set_healthy(&lb, "127.0.0.1:8080", false);
+        assert_eq!(selected(&lb, &[]), "127.0.0.1:8081");
+        set_healthy(&lb, "127.0.0.1:8080", true);
+        assert_eq!(selected(&lb, &[]), "127.0.0.1:8080");
+    }
+
+    #[tokio::test]
+    async fn test_select_slow_start_fallback() {
+        let lb = round_robin("nodes: {'127.0.0.1:8080': 1}\nslow_start: 3600").await;
+        let slow_start = &lb.upstreams.slow_starts[0];
+        let addr = "127.0.0.1:8080".parse().unwrap();
+        assert!((0..100).all(|_| !slow_start.admit(&addr)));
+
+        // A ramping node still takes requests when no other node of the tier can
+        for _ in 0..4 {
+            assert_eq!(selected(&lb, &[]), "127.0.0.1:8080");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_select_avoids_excluded() {
+        let lb = round_robin("nodes: {'127.0.0.1:8080': 1, '127.0.0.1:8081': 1}").await;
+        let excluded = ["127.0.0.1:8080".parse().unwrap()];
+        for _ in 0..4 {
+            assert_eq!(selected(&lb, &excluded), "127.0.0.1:8081");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_select_falls_back_to_excluded() {
+        let lb = round_robin("nodes: {'127.0.0.1:8080': 1, '127.0.0.1:8081': 1}").await;
+        set_healthy(&lb, "127.0.0.1:8081", false);
+        let excluded = ["127.0.0.1:8080".parse().unwrap()];
+        assert_eq!(selected(&lb, &excluded), "127.0.0.1:8080");
+
+        set_healthy(&lb, "127.0.0.1:8080", false);
+        assert!(lb.select(b"", &excluded).is_none());
+    }
+}
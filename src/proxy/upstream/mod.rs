@@ -2,12 +2,21 @@
 //!
 //! This module contains all the upstream-related functionality including:
 //! - Service discovery (DNS and static)
-//! - Load balancing and backend selection
+//! - Load balancing and backend selection, including load-aware selection
 //! - Health checking and monitoring
+//! - Passive health checking from proxied traffic
+//! - Slow start of recovered or newly added nodes
+//! - Per-upstream bounds of the keepalive connection pool
 
 pub mod discovery;
+pub mod grpc_check;
 pub mod health_check;
+pub mod http_check;
+pub mod keepalive;
 pub mod load_balancer;
+pub mod passive_check;
+pub mod selection;
+pub mod slow_start;
 
 // Re-export commonly used items
 pub use health_check::SHARED_HEALTH_CHECK_SERVICE;
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use pingora_core::protocols::l4::socket::SocketAddr;
+use pingora_error::Result;
+use pingora_load_balancing::{health_check::HealthCheck as HealthCheckTrait, Backend};
+
+use crate::{config, core::UpstreamOutcome};
+
+/// Passive health state of a single node.
+#[derive(Default)]
+struct NodeState {
+    http_failures: u32,
+    tcp_failures: u32,
+    timeouts: u32,
+    /// Consecutive successful active probes since the node was ejected.
+    probe_successes: u32,
+    ejected: bool,
+}
+
+/// Passive health check of an upstream.
+///
+/// Counts failures of proxied requests per node and ejects a node from selection once a
+/// threshold is reached. Only nodes with failures are tracked; a healthy response drops the
+/// node's counters.
+pub struct PassiveHealthCheck {
+    config: config::PassiveCheck,
+    nodes: DashMap<SocketAddr, NodeState>,
+}
+
+impl PassiveHealthCheck {
+    pub fn new(config: config::PassiveCheck) -> Self {
+        Self {
+            config,
+            nodes: DashMap::new(),
+        }
+    }
+
+    /// Whether the node is currently ejected and must not be selected.
+    pub fn is_ejected(&self, addr: &SocketAddr) -> bool {
+        self.nodes.get(addr).is_some_and(|node| node.ejected)
+    }
+
+    /// Records the outcome of a request proxied to the node.
+    pub fn report(&self, addr: &SocketAddr, outcome: UpstreamOutcome) {
+        let unhealthy = &self.config.unhealthy;
+        match outcome {
+            UpstreamOutcome::Status(status) => {
+                let status = status as u32;
+                if unhealthy.http_statuses.contains(&status) {
+                    self.record_failure(addr, unhealthy.http_failures, "HTTP failures", |node| {
+                        &mut node.http_failures
+                    });
+                } else if self.config.healthy.http_statuses.contains(&status) {
+                    self.nodes.remove_if(addr, |_, node| !node.ejected);
+                }
+            }
+            UpstreamOutcome::TcpFailure => {
+                self.record_failure(addr, unhealthy.tcp_failures, "TCP failures", |node| {
+                    &mut node.tcp_failures
+                });
+            }
+            UpstreamOutcome::Timeout => {
+                self.record_failure(addr, unhealthy.timeouts, "timeouts", |node| {
+                    &mut node.timeouts
+                });
+            }
+        }
+    }
+
+    fn record_failure(
+        &self,
+        addr: &SocketAddr,
+        threshold: u32,
+        kind: &str,
+        counter: impl FnOnce(&mut NodeState) -> &mut u32,
+    ) {
+        // A zero threshold disables this kind of failure
+        if threshold == 0 {
+            return;
+        }
+
+        let mut node = self.nodes.entry(addr.clone()).or_default();
+        if node.ejected {
+            return;
+        }
+
+        let count = counter(&mut node);
+        *count += 1;
+        if *count >= threshold {
+            *node = NodeState {
+                ejected: true,
+                ..Default::default()
+            };
+            log::warn!("Passive health check ejected node {addr} after {threshold} {kind}");
+        }
+    }
+
+    /// Records an active probe result; ejected nodes return after enough consecutive successes.
+    fn observe_probe(&self, addr: &SocketAddr, success: bool) {
+        let Some(mut node) = self.nodes.get_mut(addr) else {
+            return;
+        };
+        if !node.ejected {
+            return;
+        }
+
+        if !success {
+            node.probe_successes = 0;
+            return;
+        }
+
+        node.probe_successes += 1;
+        if node.probe_successes >= self.config.healthy.successes {
+            drop(node);
+            self.nodes.remove(addr);
+            log::info!("Passive health check restored node {addr}");
+        }
+    }
+}
+
+/// Active health check that also feeds probe results to the passive health check.
+pub struct PassiveAwareHealthCheck {
+    inner: Box<dyn HealthCheckTrait + Send + Sync + 'static>,
+    passive: Arc<PassiveHealthCheck>,
+}
+
+impl PassiveAwareHealthCheck {
+    pub fn new(
+        inner: Box<dyn HealthCheckTrait + Send + Sync + 'static>,
+        passive: Arc<PassiveHealthCheck>,
+    ) -> Self {
+        Self { inner, passive }
+    }
+}
+
+#[async_trait]
+impl HealthCheckTrait for PassiveAwareHealthCheck {
+    async fn check(&self, target: &Backend) -> Result<()> {
+        let result = self.inner.check(target).await;
+        self.passive.observe_probe(&target.addr, result.is_ok());
+        result
+    }
+
+    async fn health_status_change(&self, target: &Backend, healthy: bool) {
+        self.inner.health_status_change(target, healthy).await;
+    }
+
+    fn backend_summary(&self, target: &Backend) -> String {
+        self.inner.backend_summary(target)
+    }
+
+    fn health_threshold(&self, success: bool) -> usize {
+        self.inner.health_threshold(success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passive_check(yaml: &str) -> PassiveHealthCheck {
+        PassiveHealthCheck::new(serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_passive_check_ejects_and_restores() {
+        let check = passive_check(
+            "unhealthy: {http_failures: 2, tcp_failures: 1}\nhealthy: {successes: 2}",
+        );
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+
+        check.report(&addr, UpstreamOutcome::Status(500));
+        check.report(&addr, UpstreamOutcome::Status(200));
+        check.report(&addr, UpstreamOutcome::Status(500));
+        assert!(!check.is_ejected(&addr));
+        check.report(&addr, UpstreamOutcome::Status(503));
+        assert!(check.is_ejected(&addr));
+
+        check.observe_probe(&addr, true);
+        check.observe_probe(&addr, false);
+        check.observe_probe(&addr, true);
+        assert!(check.is_ejected(&addr));
+        check.observe_probe(&addr, true);
+        assert!(!check.is_ejected(&addr));
+
+        check.report(&addr, UpstreamOutcome::TcpFailure);
+        assert!(check.is_ejected(&addr));
+    }
+
+    #[test]
+    fn test_passive_check_zero_threshold_disabled() {
+        let check = passive_check("unhealthy: {timeouts: 0}");
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+
+        for _ in 0..10 {
+            check.report(&addr, UpstreamOutcome::Timeout);
+        }
+        assert!(!check.is_ejected(&addr));
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::BTreeSet,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use pingora_core::protocols::l4::socket::SocketAddr;
+use pingora_load_balancing::{
+    selection::{BackendIter, BackendSelection},
+    Backend,
+};
+
+use crate::core::InflightRequest;
+
+/// How fast the latency average forgets old samples.
+const EWMA_DECAY_TIME: Duration = Duration::from_secs(10);
+
+/// Load of a single node.
+#[derive(Default)]
+pub struct NodeStats {
+    inflight: AtomicUsize,
+    /// Average response time in microseconds and when it was last updated.
+    ewma: Mutex<Option<(f64, Instant)>>,
+}
+
+impl NodeStats {
+    fn inflight(&self) -> usize {
+        self.inflight.load(Ordering::Relaxed)
+    }
+
+    /// Average response time, decayed towards zero since the last sample so idle nodes
+    /// get retried.
+    fn ewma_at(&self, now: Instant) -> f64 {
+        match *self.ewma.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some((ewma, updated)) => ewma * decay_weight(now.saturating_duration_since(updated)),
+            None => 0.0,
+        }
+    }
+
+    fn observe(&self, response_time: Duration) {
+        let now = Instant::now();
+        let sample = response_time.as_micros() as f64;
+        let mut ewma = self.ewma.lock().unwrap_or_else(|e| e.into_inner());
+        *ewma = Some(match *ewma {
+            Some((avg, updated)) => {
+                let weight = decay_weight(now.saturating_duration_since(updated));
+                (avg * weight + sample * (1.0 - weight), now)
+            }
+            None => (sample, now),
+        });
+    }
+}
+
+fn decay_weight(elapsed: Duration) -> f64 {
+    (-elapsed.as_secs_f64() / EWMA_DECAY_TIME.as_secs_f64()).exp()
+}
+
+/// Load statistics of an upstream's nodes, shared by the selector and in-flight requests.
+#[derive(Default)]
+pub struct NodeLoad {
+    nodes: DashMap<SocketAddr, Arc<NodeStats>>,
+}
+
+impl NodeLoad {
+    fn stats(&self, addr: &SocketAddr) -> Arc<NodeStats> {
+        self.nodes.entry(addr.clone()).or_default().clone()
+    }
+
+    /// Counts a request to the node as in flight until the returned guard is dropped.
+    pub fn start_request(&self, addr: &SocketAddr) -> NodeRequest {
+        let stats = self.stats(addr);
+        stats.inflight.fetch_add(1, Ordering::Relaxed);
+        NodeRequest {
+            stats,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// A request in flight to a node.
+pub struct NodeRequest {
+    stats: Arc<NodeStats>,
+    start: Instant,
+}
+
+impl InflightRequest for NodeRequest {
+    fn complete(&mut self) {
+        self.stats.observe(self.start.elapsed());
+    }
+}
+
+impl Drop for NodeRequest {
+    fn drop(&mut self) {
+        self.stats.inflight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Load score of a node; lower is better.
+pub trait LoadScore {
+    fn score(stats: &NodeStats, now: Instant) -> f64;
+}
+
+/// Scores nodes by in-flight requests.
+pub struct LeastConn;
+
+impl LoadScore for LeastConn {
+    fn score(stats: &NodeStats, _now: Instant) -> f64 {
+        (stats.inflight() + 1) as f64
+    }
+}
+
+/// Scores nodes by average response time times in-flight requests.
+pub struct Ewma;
+
+impl LoadScore for Ewma {
+    fn score(stats: &NodeStats, now: Instant) -> f64 {
+        (stats.ewma_at(now) + 1.0) * (stats.inflight() + 1) as f64
+    }
+}
+
+/// Selection trying backends in order of their weighted load score.
+///
+/// Backends with equal scores are tried in round-robin order so idle nodes share traffic.
+pub struct LoadAware<S> {
+    backends: Vec<(Backend, Arc<NodeStats>)>,
+    counter: AtomicUsize,
+    _score: PhantomData<S>,
+}
+
+pub type LeastConnection = LoadAware<LeastConn>;
+pub type EwmaLatency = LoadAware<Ewma>;
+
+impl<S: LoadScore> BackendSelection for LoadAware<S> {
+    type Iter = LoadAwareIter<S>;
+    type Config = Arc<NodeLoad>;
+
+    fn build_with_config(backends: &BTreeSet<Backend>, load: &Self::Config) -> Self {
+        // Forget nodes that left the upstream. Other priority tiers share `load`, and their
+        // selections hold references to the stats of their own nodes.
+        load.nodes.retain(|addr, stats| {
+            Arc::strong_count(stats) > 1 || backends.iter().any(|backend| &backend.addr == addr)
+        });
+
+        Self {
+            backends: backends
+                .iter()
+                .map(|backend| (backend.clone(), load.stats(&backend.addr)))
+                .collect(),
+            counter: AtomicUsize::new(0),
+            _score: PhantomData,
+        }
+    }
+
+    fn build(backends: &BTreeSet<Backend>) -> Self {
+        Self::build_with_config(backends, &Arc::default())
+    }
+
+    fn iter(self: &Arc<Self>, _key: &[u8]) -> Self::Iter {
+        let now = Instant::now();
+        let len = self.backends.len();
+        let offset = self.counter.fetch_add(1, Ordering::Relaxed);
+
+        let mut scored: Vec<(f64, usize)> = (0..len)
+            .map(|i| {
+                let index = (offset + i) % len;
+                let (backend, stats) = &self.backends[index];
+                (S::score(stats, now) / backend.weight.max(1) as f64, index)
+            })
+            .collect();
+        // Stable sort keeps the rotated order among equal scores
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        LoadAwareIter {
+            selection: self.clone(),
+            order: scored.into_iter().map(|(_, index)| index).collect(),
+            position: 0,
+        }
+    }
+}
+
+pub struct LoadAwareIter<S> {
+    selection: Arc<LoadAware<S>>,
+    order: Vec<usize>,
+    position: usize,
+}
+
+impl<S> BackendIter for LoadAwareIter<S> {
+    fn next(&mut self) -> Option<&Backend> {
+        let index = *self.order.get(self.position)?;
+        self.position += 1;
+        Some(&self.selection.backends[index].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection<S: LoadScore>(load: &Arc<NodeLoad>) -> Arc<LoadAware<S>> {
+        let backends = BTreeSet::from([
+            Backend::new("127.0.0.1:8001").unwrap(),
+            Backend::new("127.0.0.1:8002").unwrap(),
+        ]);
+        Arc::new(LoadAware::build_with_config(&backends, load))
+    }
+
+    fn first<S: LoadScore>(selection: &Arc<LoadAware<S>>) -> String {
+        selection.iter(b"").next().unwrap().addr.to_string()
+    }
+
+    #[test]
+    fn test_least_conn_prefers_idle_node() {
+        let load = Arc::new(NodeLoad::default());
+        let selection = selection::<LeastConn>(&load);
+
+        // Idle nodes take turns
+        assert_ne!(first(&selection), first(&selection));
+
+        let busy = load.start_request(&"127.0.0.1:8001".parse().unwrap());
+        for _ in 0..4 {
+            assert_eq!(first(&selection), "127.0.0.1:8002");
+        }
+
+        drop(busy);
+        assert_ne!(first(&selection), first(&selection));
+    }
+
+    #[test]
+    fn test_ewma_prefers_fast_node() {
+        let load = Arc::new(NodeLoad::default());
+        let selection = selection::<Ewma>(&load);
+
+        load.stats(&"127.0.0.1:8001".parse().unwrap())
+            .observe(Duration::from_millis(200));
+        load.stats(&"127.0.0.1:8002".parse().unwrap())
+            .observe(Duration::from_millis(5));
+        for _ in 0..4 {
+            assert_eq!(first(&selection), "127.0.0.1:8002");
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use pingora_core::protocols::l4::socket::SocketAddr;
+use pingora_error::Result;
+use pingora_load_balancing::{
+    discovery::ServiceDiscovery, health_check::HealthCheck as HealthCheckTrait, Backend,
+};
+use rand::Rng;
+
+use super::passive_check::PassiveHealthCheck;
+
+/// Slow start of a priority tier's nodes.
+///
+/// A node's share of traffic ramps linearly from zero to its full weight over `duration`
+/// after it joins the tier or turns healthy again.
+pub struct SlowStart {
+    duration: Duration,
+    /// When each node of the tier joined or last recovered.
+    nodes: DashMap<SocketAddr, Instant>,
+}
+
+impl SlowStart {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            nodes: DashMap::new(),
+        }
+    }
+
+    /// Fraction of its weight the node currently gets, from 0 to 1.
+    fn ramp(&self, addr: &SocketAddr, now: Instant) -> f64 {
+        let Some(started) = self.nodes.get(addr) else {
+            return 1.0;
+        };
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        let elapsed = now.saturating_duration_since(*started);
+        (elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+    }
+
+    /// Whether a node picked by the selection takes the request. A ramping node takes the
+    /// fraction of its picks given by its ramp, so its effective weight grows linearly.
+    pub fn admit(&self, addr: &SocketAddr) -> bool {
+        let ramp = self.ramp(addr, Instant::now());
+        ramp >= 1.0 || rand::thread_rng().gen::<f64>() < ramp
+    }
+
+    /// Starts ramping nodes the tier did not have before and forgets nodes it lost.
+    fn observe_nodes(&self, backends: &BTreeSet<Backend>) {
+        let now = Instant::now();
+        self.nodes
+            .retain(|addr, _| backends.iter().any(|backend| &backend.addr == addr));
+        for backend in backends {
+            self.nodes.entry(backend.addr.clone()).or_insert(now);
+        }
+    }
+
+    fn restart(&self, addr: &SocketAddr) {
+        self.nodes.insert(addr.clone(), Instant::now());
+        log::info!("Slow start of node {addr} restarted");
+    }
+}
+
+/// Discovery that starts the slow start of newly discovered nodes.
+pub struct SlowStartDiscovery {
+    inner: Box<dyn ServiceDiscovery + Send + Sync>,
+    slow_start: Arc<SlowStart>,
+}
+
+impl SlowStartDiscovery {
+    pub fn new(inner: Box<dyn ServiceDiscovery + Send + Sync>, slow_start: Arc<SlowStart>) -> Self {
+        Self { inner, slow_start }
+    }
+}
+
+#[async_trait]
+impl ServiceDiscovery for SlowStartDiscovery {
+    async fn discover(&self) -> Result<(BTreeSet<Backend>, HashMap<u64, bool>)> {
+        let discovered = self.inner.discover().await?;
+        self.slow_start.observe_nodes(&discovered.0);
+        Ok(discovered)
+    }
+}
+
+/// Active health check that restarts the slow start of nodes turning healthy again, including
+/// nodes restored by the passive health check.
+pub struct SlowStartHealthCheck {
+    inner: Box<dyn HealthCheckTrait + Send + Sync + 'static>,
+    slow_start: Arc<SlowStart>,
+    passive: Option<Arc<PassiveHealthCheck>>,
+}
+
+impl SlowStartHealthCheck {
+    pub fn new(
+        inner: Box<dyn HealthCheckTrait + Send + Sync + 'static>,
+        slow_start: Arc<SlowStart>,
+        passive: Option<Arc<PassiveHealthCheck>>,
+    ) -> Self {
+        Self {
+            inner,
+            slow_start,
+            passive,
+        }
+    }
+
+    fn is_ejected(&self, addr: &SocketAddr) -> bool {
+        self.passive
+            .as_ref()
+            .is_some_and(|passive| passive.is_ejected(addr))
+    }
+}
+
+#[async_trait]
+impl HealthCheckTrait for SlowStartHealthCheck {
+    async fn check(&self, target: &Backend) -> Result<()> {
+        let ejected = self.is_ejected(&target.addr);
+        let result = self.inner.check(target).await;
+        if ejected && !self.is_ejected(&target.addr) {
+            self.slow_start.restart(&target.addr);
+        }
+        result
+    }
+
+    async fn health_status_change(&self, target: &Backend, healthy: bool) {
+        if healthy {
+            self.slow_start.restart(&target.addr);
+        }
+        self.inner.health_status_change(target, healthy).await;
+    }
+
+    fn backend_summary(&self, target: &Backend) -> String {
+        self.inner.backend_summary(target)
+    }
+
+    fn health_threshold(&self, success: bool) -> usize {
+        self.inner.health_threshold(success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_start_ramp() {
+        let slow_start = SlowStart::new(Duration::from_secs(10));
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let now = Instant::now();
+
+        // Unknown nodes get their full weight
+        assert_eq!(slow_start.ramp(&addr, now), 1.0);
+
+        slow_start.observe_nodes(&BTreeSet::from([Backend::new("127.0.0.1:8080").unwrap()]));
+        let started = *slow_start.nodes.get(&addr).unwrap();
+        assert!(slow_start.ramp(&addr, started) < 0.01);
+        let half = slow_start.ramp(&addr, started + Duration::from_secs(5));
+        assert!((half - 0.5).abs() < 1e-9);
+        assert_eq!(
+            slow_start.ramp(&addr, started + Duration::from_secs(20)),
+            1.0
+        );
+
+        // Nodes already in the tier keep ramping from when they joined
+        slow_start.observe_nodes(&BTreeSet::from([Backend::new("127.0.0.1:8080").unwrap()]));
+        assert_eq!(*slow_start.nodes.get(&addr).unwrap(), started);
+
+        slow_start.observe_nodes(&BTreeSet::new());
+        assert_eq!(slow_start.ramp(&addr, started), 1.0);
+    }
+}
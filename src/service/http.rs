@@ -17,14 +17,16 @@ use pingora_cache::{
     CacheMeta, CacheMetaDefaults, CachePhase, MemCache, NoCacheReason, RespCacheable,
     VarianceBuilder,
 };
-use pingora_core::upstreams::peer::HttpPeer;
-use pingora_error::{Error, Result};
+use pingora_core::{protocols::Digest, upstreams::peer::HttpPeer};
+use pingora_error::{Error, ErrorSource, ErrorType, Result};
 use pingora_http::{RequestHeader, ResponseHeader};
 use pingora_proxy::{ProxyHttp, Session};
 
 use crate::{
     config,
-    core::{ProxyContext, ProxyError, ProxyPlugin, RouteContext},
+    core::{
+        ProxyContext, ProxyError, ProxyPlugin, RouteContext, UpstreamOutcome, UpstreamSelector,
+    },
     plugins::cache::{CacheSettings, CTX_KEY_CACHE_SETTINGS},
     proxy::{global_rule::global_plugin_fetch, route::global_route_match_fetch},
 };
@@ -120,23 +122,36 @@ impl ProxyHttp for HttpService {
         session: &mut Session,
         ctx: &mut Self::CTX,
     ) -> Result<Box<HttpPeer>> {
-        let peer = if let Some(ups_override) = ctx.upstream_override.as_ref() {
-            let mut backend = ups_override.select_backend(session).ok_or_else(|| {
-                ProxyError::UpstreamSelection("Traffic-split selected no backend".to_string())
-            })?;
-
-            backend
-                .ext
-                .get_mut::<HttpPeer>()
-                .ok_or_else(|| ProxyError::Internal("Peer missing".into()))
-                .map(|p| Box::new(p.clone()))?
+        let backend = if let Some(ups_override) = ctx.upstream_override.as_ref() {
+            ups_override
+                .select_backend(session, &ctx.failed_peers)
+                .ok_or_else(|| {
+                    ProxyError::UpstreamSelection("Traffic-split selected no backend".to_string())
+                })?
         } else {
             ctx.route
                 .as_ref()
                 .ok_or_else(|| ProxyError::Internal("Route not found".into()))
-                .and_then(|r| r.select_http_peer(session))?
+                .and_then(|r| r.select_backend(session, &ctx.failed_peers))?
         };
 
+        let mut peer = backend
+            .ext
+            .get::<HttpPeer>()
+            .ok_or_else(|| ProxyError::Internal("Peer missing".into()))
+            .map(|p| Box::new(p.clone()))?;
+        ctx.node_metadata = backend.ext.get::<Arc<config::NodeMetadata>>().cloned();
+
+        // Upgraded connections are long-lived, so they get their own timeouts
+        if session.is_upgrade_req() {
+            if let Some(timeout) = ctx.route.as_ref().and_then(|r| r.websocket_timeout()) {
+                peer.options.read_timeout = Some(Duration::from_secs(timeout.read));
+                session.set_read_timeout(Some(Duration::from_secs(timeout.idle)));
+            }
+        }
+
+        // Replacing the tracker on retries releases the previous node
+        ctx.inflight = selected_upstream(ctx).and_then(|u| u.start_request(&peer));
         ctx.peer = Some(peer.clone());
         Ok(peer)
     }
@@ -160,15 +175,26 @@ impl ProxyHttp for HttpService {
             .upstream_request_filter(session, upstream_request, ctx)
             .await?;
 
-        // Rewrite host header
-        // Priority: upstream_override > route upstream
-        let upstream = ctx
-            .upstream_override
+        // With `enable_websocket: false`, upgrade requests are proxied as plain requests
+        if session.is_upgrade_req() && ctx.route.as_ref().is_some_and(|r| r.websocket_disabled()) {
+            upstream_request.remove_header(&http::header::UPGRADE);
+            upstream_request.remove_header(&http::header::CONNECTION);
+        }
+
+        // HTTP/2 connections are multiplexed and upgraded ones never go back to the pool, so
+        // only other HTTP/1.1 connections count against the upstream's keepalive pool
+        if upstream_request.version == http::Version::HTTP_2 || session.is_upgrade_req() {
+            ctx.pooled_connection = None;
+        } else if ctx
+            .pooled_connection
             .as_ref()
-            .map(|u| u.clone() as Arc<dyn crate::core::UpstreamSelector>)
-            .or_else(|| ctx.route.as_ref().and_then(|r| r.resolve_upstream()));
+            .is_some_and(|c| !c.keepalive())
+        {
+            upstream_request.insert_header(http::header::CONNECTION, "close")?;
+        }
 
-        if let Some(upstream) = upstream {
+        // Rewrite host header
+        if let Some(upstream) = selected_upstream(ctx) {
             match upstream.get_pass_host() {
                 config::UpstreamPassHost::PASS => {
                     // Do nothing, preserve original host
@@ -372,36 +398,224 @@ impl ProxyHttp for HttpService {
 
         // Execute plugins
         ctx.plugin.clone().logging(session, e, ctx).await;
+
+        // Feed passive health checks; connect failures are reported in fail_to_connect and
+        // retried failures when the retry is decided
+        if let (Some(peer), Some(upstream)) = (ctx.peer.as_deref(), selected_upstream(ctx)) {
+            let outcome = match e {
+                None => session
+                    .response_written()
+                    .map(|resp| UpstreamOutcome::Status(resp.status.as_u16())),
+                Some(e) => upstream_error_outcome(e),
+            };
+            if let Some(outcome) = outcome {
+                upstream.report_outcome(peer, outcome);
+            }
+        }
+
+        // Upgraded connections are long-lived, so their duration is not a response time
+        if let Some(mut inflight) = ctx.inflight.take() {
+            if e.is_none() && !session.was_upgraded() {
+                inflight.complete();
+            }
+        }
+
+        // Connections of failed requests are not reused
+        if let Some(mut connection) = ctx.pooled_connection.take() {
+            if e.is_none() {
+                connection.release();
+            }
+        }
+    }
+
+    /// Counts the upstream connection against the upstream's keepalive pool, if bounded.
+    async fn connected_to_upstream(
+        &self,
+        _session: &mut Session,
+        _reused: bool,
+        _peer: &HttpPeer,
+        #[cfg(unix)] fd: std::os::unix::io::RawFd,
+        #[cfg(windows)] sock: std::os::windows::io::RawSocket,
+        _digest: Option<&Digest>,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        #[cfg(unix)]
+        let connection = fd as u64;
+        #[cfg(windows)]
+        let connection = sock;
+        // Replacing the slot on retries releases the previous connection
+        ctx.pooled_connection = selected_upstream(ctx).and_then(|u| u.pool_connection(connection));
+        Ok(())
     }
 
     /// This filter is called when there is an error in the process of establishing a connection to the upstream.
     fn fail_to_connect(
         &self,
         _session: &mut Session,
-        _peer: &HttpPeer,
+        peer: &HttpPeer,
         ctx: &mut Self::CTX,
         mut e: Box<Error>,
     ) -> Box<Error> {
-        if let Some(route) = ctx.route.as_ref() {
-            if let Some(upstream) = route.resolve_upstream() {
-                if let Some(retries) = upstream.get_retries() {
-                    if retries > 0 && ctx.tries < retries {
-                        let within_timeout = match upstream.get_retry_timeout() {
-                            Some(timeout) => ctx.elapsed_ms() <= (timeout * 1000) as u128,
-                            None => true,
-                        };
-                        if within_timeout {
-                            ctx.tries += 1;
-                            e.set_retry(true);
-                        }
-                    }
+        if let Some(upstream) = selected_upstream(ctx) {
+            let outcome = match e.etype() {
+                ErrorType::ConnectTimedout => UpstreamOutcome::Timeout,
+                _ => UpstreamOutcome::TcpFailure,
+            };
+            upstream.report_outcome(peer, outcome);
+        }
+
+        if take_retry(ctx, peer) {
+            e.set_retry(true);
+        }
+        e
+    }
+
+    /// Retries the request on another node if the upstream status is listed in `retry_on`.
+    async fn upstream_response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        // A node closing the connection takes it out of the keepalive pool
+        if upstream_response
+            .headers
+            .get(http::header::CONNECTION)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"close"))
+        {
+            ctx.pooled_connection = None;
+        }
+
+        let status = upstream_response.status.as_u16();
+        let Some(upstream) = selected_upstream(ctx) else {
+            return Ok(());
+        };
+        let Some(retry_on) = upstream.get_retry_on() else {
+            return Ok(());
+        };
+
+        if retry_on.http_statuses.contains(&(status as u32)) && request_retryable(session, retry_on)
+        {
+            if let Some(peer) = ctx.peer.clone() {
+                if take_retry(ctx, &peer) {
+                    upstream.report_outcome(&peer, UpstreamOutcome::Status(status));
+                    let mut e = Error::explain(
+                        ErrorType::HTTPStatus(status),
+                        format!("Retrying upstream response status {status}"),
+                    );
+                    e.as_up();
+                    e.set_retry(true);
+                    return Err(e);
                 }
             }
         }
+
+        Ok(())
+    }
+
+    /// Retries read timeouts and connection resets listed in `retry_on` on another node.
+    fn error_while_proxy(
+        &self,
+        peer: &HttpPeer,
+        session: &mut Session,
+        e: Box<Error>,
+        ctx: &mut Self::CTX,
+        client_reused: bool,
+    ) -> Box<Error> {
+        let mut e = e.more_context(format!("Peer: {peer}"));
+        // Pingora default: retry failures of reused connections if the body can be replayed
+        e.retry
+            .decide_reuse(client_reused && !session.as_ref().retry_buffer_truncated());
+        if e.retry()
+            || e.esource() != &ErrorSource::Upstream
+            || session.response_written().is_some()
+        {
+            return e;
+        }
+
+        let Some(upstream) = selected_upstream(ctx) else {
+            return e;
+        };
+        let Some(retry_on) = upstream.get_retry_on() else {
+            return e;
+        };
+
+        let outcome = upstream_error_outcome(&e);
+        let retry = match outcome {
+            Some(UpstreamOutcome::Timeout) => {
+                retry_on.timeout && e.etype() == &ErrorType::ReadTimedout
+            }
+            Some(UpstreamOutcome::TcpFailure) => retry_on.connection_reset,
+            _ => false,
+        };
+        if retry && request_retryable(session, retry_on) && take_retry(ctx, peer) {
+            if let Some(outcome) = outcome {
+                upstream.report_outcome(peer, outcome);
+            }
+            e.set_retry(true);
+        }
         e
     }
 }
 
+/// Counts a retry of the request away from `peer`, if the upstream's `retries` and
+/// `retry_timeout` allow another attempt.
+fn take_retry(ctx: &mut ProxyContext, peer: &HttpPeer) -> bool {
+    let Some(upstream) = selected_upstream(ctx) else {
+        return false;
+    };
+    let Some(retries) = upstream.get_retries() else {
+        return false;
+    };
+    if ctx.tries >= retries {
+        return false;
+    }
+
+    let within_timeout = match upstream.get_retry_timeout() {
+        Some(timeout) => ctx.elapsed_ms() <= (timeout * 1000) as u128,
+        None => true,
+    };
+    if within_timeout {
+        ctx.tries += 1;
+        ctx.failed_peers.push(peer._address.clone());
+    }
+    within_timeout
+}
+
+/// Whether the request may be sent again after the upstream has seen it: the method must be
+/// idempotent unless `non_idempotent` is set, and the request body must still be buffered.
+fn request_retryable(session: &Session, retry_on: &config::RetryOn) -> bool {
+    method_retryable(&session.req_header().method, retry_on)
+        && !session.as_ref().retry_buffer_truncated()
+}
+
+fn method_retryable(method: &http::Method, retry_on: &config::RetryOn) -> bool {
+    retry_on.non_idempotent || method.is_idempotent()
+}
+
+/// Passive health check outcome of a failed upstream read or write.
+fn upstream_error_outcome(e: &Error) -> Option<UpstreamOutcome> {
+    if e.esource() != &ErrorSource::Upstream {
+        return None;
+    }
+    match e.etype() {
+        ErrorType::ReadTimedout | ErrorType::WriteTimedout => Some(UpstreamOutcome::Timeout),
+        ErrorType::ReadError | ErrorType::WriteError | ErrorType::ConnectionClosed => {
+            Some(UpstreamOutcome::TcpFailure)
+        }
+        _ => None,
+    }
+}
+
+/// Upstream the request is proxied to.
+///
+/// Priority: upstream_override > route upstream
+fn selected_upstream(ctx: &ProxyContext) -> Option<Arc<dyn UpstreamSelector>> {
+    ctx.upstream_override
+        .clone()
+        .or_else(|| ctx.route.as_ref().and_then(|r| r.resolve_upstream()))
+}
+
 /// Ensures CacheControl has max-age set, adding default TTL if missing.
 /// Also handles s-maxage and stale-while-revalidate directives based on settings.
 fn ensure_max_age(cc: Option<CacheControl>, settings: &CacheSettings) -> Option<CacheControl> {
@@ -468,3 +682,119 @@ fn ensure_max_age(cc: Option<CacheControl>, settings: &CacheSettings) -> Option<
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use http::Method;
+    use pingora_core::protocols::l4::socket::SocketAddr;
+    use pingora_load_balancing::Backend;
+
+    use super::*;
+    use crate::core::{InflightRequest, PooledConnection};
+
+    struct RetryUpstream {
+        retries: Option<usize>,
+        retry_timeout: Option<u64>,
+        pass_host: config::UpstreamPassHost,
+    }
+
+    impl UpstreamSelector for RetryUpstream {
+        fn select_backend(&self, _: &mut Session, _: &[SocketAddr]) -> Option<Backend> {
+            None
+        }
+
+        fn get_retries(&self) -> Option<usize> {
+            self.retries
+        }
+
+        fn get_retry_timeout(&self) -> Option<u64> {
+            self.retry_timeout
+        }
+
+        fn get_retry_on(&self) -> Option<&config::RetryOn> {
+            None
+        }
+
+        fn get_pass_host(&self) -> &config::UpstreamPassHost {
+            &self.pass_host
+        }
+
+        fn upstream_host_rewrite(&self, _: &mut RequestHeader) {}
+
+        fn report_outcome(&self, _: &HttpPeer, _: UpstreamOutcome) {}
+
+        fn start_request(&self, _: &HttpPeer) -> Option<Box<dyn InflightRequest>> {
+            None
+        }
+
+        fn pool_connection(&self, _: u64) -> Option<Box<dyn PooledConnection>> {
+            None
+        }
+    }
+
+    fn retry_ctx(retries: Option<usize>, retry_timeout: Option<u64>) -> ProxyContext {
+        ProxyContext {
+            upstream_override: Some(Arc::new(RetryUpstream {
+                retries,
+                retry_timeout,
+                pass_host: config::UpstreamPassHost::default(),
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_method_retryable() {
+        let retry_on = config::RetryOn::default();
+        assert!(method_retryable(&Method::GET, &retry_on));
+        assert!(method_retryable(&Method::PUT, &retry_on));
+        assert!(method_retryable(&Method::DELETE, &retry_on));
+        assert!(!method_retryable(&Method::POST, &retry_on));
+        assert!(!method_retryable(&Method::PATCH, &retry_on));
+
+        let retry_on = config::RetryOn {
+            non_idempotent: true,
+            ..Default::default()
+        };
+        assert!(method_retryable(&Method::POST, &retry_on));
+        assert!(method_retryable(&Method::PATCH, &retry_on));
+    }
+
+    #[test]
+    fn test_take_retry_exhausts_retries() {
+        let first = HttpPeer::new("127.0.0.1:8080", false, String::new());
+        let second = HttpPeer::new("127.0.0.1:8081", false, String::new());
+
+        let mut ctx = retry_ctx(Some(2), None);
+        assert!(take_retry(&mut ctx, &first));
+        assert!(take_retry(&mut ctx, &second));
+        assert!(!take_retry(&mut ctx, &first));
+        assert_eq!(ctx.tries, 2);
+        assert_eq!(
+            ctx.failed_peers,
+            vec![first._address.clone(), second._address.clone()]
+        );
+
+        // Retries are off without `retries` or an upstream
+        let mut ctx = retry_ctx(None, None);
+        assert!(!take_retry(&mut ctx, &first));
+        let mut ctx = ProxyContext::default();
+        assert!(!take_retry(&mut ctx, &first));
+        assert!(ctx.failed_peers.is_empty());
+    }
+
+    #[test]
+    fn test_take_retry_exhausts_retry_timeout() {
+        let peer = HttpPeer::new("127.0.0.1:8080", false, String::new());
+
+        let mut ctx = retry_ctx(Some(3), Some(1));
+        assert!(take_retry(&mut ctx, &peer));
+
+        ctx.request_start = Instant::now() - Duration::from_secs(2);
+        assert!(!take_retry(&mut ctx, &peer));
+        assert_eq!(ctx.tries, 1);
+        assert_eq!(ctx.failed_peers.len(), 1);
+    }
+}
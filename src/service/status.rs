@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
 use http::{Response, StatusCode};
 use pingora::{
@@ -5,7 +7,11 @@ use pingora::{
 };
 use serde::Serialize;
 
-use crate::{config::Status, core::status};
+use crate::{
+    config::Status,
+    core::status,
+    proxy::upstream::{health_check::NodeHealth, SHARED_HEALTH_CHECK_SERVICE, UPSTREAM_MAP},
+};
 
 #[derive(Serialize)]
 struct StatusResponse {
@@ -14,12 +20,26 @@ struct StatusResponse {
     error: Option<String>,
 }
 
+#[derive(Serialize)]
+struct UpstreamHealthResponse {
+    id: String,
+    nodes: Vec<NodeHealth>,
+}
+
+#[derive(Serialize)]
+struct UpstreamsHealthResponse {
+    upstreams: Vec<UpstreamHealthResponse>,
+}
+
 /// HTTP application for serving status/health check endpoints.
 ///
 /// This service provides a simple readiness probe endpoint that returns:
 /// - 200 OK with {"status": "ok"} when configuration is loaded
 /// - 503 Service Unavailable with error details when not ready
 ///
+/// `/status/upstreams` and `/status/upstreams/{id}` report the health of every upstream
+/// node as seen by the load balancer.
+///
 /// This is intentionally separate from the admin API so it can be used
 /// independently for Kubernetes readiness probes, load balancer health checks, etc.
 pub struct StatusHttpApp {
@@ -51,7 +71,11 @@ impl ServeHttp for StatusHttpApp {
 
         match path {
             "/status/ready" => handle_ready_endpoint(),
-            _ => not_found_response(),
+            "/status/upstreams" => handle_upstreams_endpoint(),
+            _ => match path.strip_prefix("/status/upstreams/") {
+                Some(id) if !id.is_empty() => handle_upstream_endpoint(id),
+                _ => not_found_response(),
+            },
         }
     }
 }
@@ -72,6 +96,47 @@ fn handle_ready_endpoint() -> Response<Vec<u8>> {
     }
 }
 
+/// Node health of every upstream, by ID.
+///
+/// Inline upstreams of routes and services are only known to the health check registry.
+fn upstreams_health() -> BTreeMap<String, Vec<NodeHealth>> {
+    let mut upstreams: BTreeMap<_, _> = SHARED_HEALTH_CHECK_SERVICE
+        .upstreams_health()
+        .into_iter()
+        .collect();
+    for upstream in UPSTREAM_MAP.iter() {
+        upstreams.insert(upstream.key().clone(), upstream.value().nodes_health());
+    }
+    upstreams
+}
+
+fn handle_upstreams_endpoint() -> Response<Vec<u8>> {
+    let response = UpstreamsHealthResponse {
+        upstreams: upstreams_health()
+            .into_iter()
+            .map(|(id, nodes)| UpstreamHealthResponse { id, nodes })
+            .collect(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+fn handle_upstream_endpoint(id: &str) -> Response<Vec<u8>> {
+    let nodes = match UPSTREAM_MAP.get(id) {
+        Some(upstream) => Some(upstream.value().nodes_health()),
+        None => SHARED_HEALTH_CHECK_SERVICE.upstream_health(id),
+    };
+    match nodes {
+        Some(nodes) => json_response(
+            StatusCode::OK,
+            &UpstreamHealthResponse {
+                id: id.to_string(),
+                nodes,
+            },
+        ),
+        None => not_found_response(),
+    }
+}
+
 fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Vec<u8>> {
     let json_body = serde_json::to_vec(body).unwrap_or_else(|e| {
         log::error!("Failed to serialize status response: {e}");
@@ -0,0 +1,563 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use pingora::{
+    apps::ServerApp, protocols::Stream, server::ShutdownWatch, services::listening::Service,
+};
+use pingora_core::{
+    protocols::l4::socket::SocketAddr as BackendAddr, services::Service as ServiceTrait,
+};
+use tokio::{
+    io::{copy_bidirectional, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, UdpSocket, UnixStream},
+    time::timeout,
+};
+
+use crate::{
+    config::StreamProxy,
+    core::{ProxyError, ProxyResult, UpstreamOutcome},
+    proxy::{
+        stream_route::{stream_route_match, stream_routes_need_sni},
+        upstream::ProxyUpstream,
+    },
+};
+
+/// Used when the upstream has no `timeout.connect`.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long to wait for a TLS ClientHello when a route matches on SNI.
+const CLIENT_HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+/// A ClientHello fits in a single TLS record.
+const MAX_CLIENT_HELLO_LEN: usize = 5 + 16384;
+const MAX_DATAGRAM_LEN: usize = 65535;
+/// Pause after a failed UDP receive, so a persistent socket error does not spin.
+const UDP_RECV_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Connection to a stream backend, over TCP or a Unix domain socket.
+trait UpstreamIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> UpstreamIo for T {}
+
+/// TCP application forwarding connections to the upstream of the matched stream route.
+pub struct StreamTcpApp;
+
+impl StreamTcpApp {
+    pub fn stream_tcp_service(cfg: &StreamProxy) -> Service<Self> {
+        let mut service = Service::new("Stream TCP".to_string(), StreamTcpApp);
+        for addr in cfg.tcp.iter() {
+            service.add_tcp(&addr.to_string());
+        }
+        service
+    }
+}
+
+#[async_trait]
+impl ServerApp for StreamTcpApp {
+    async fn process_new(
+        self: &Arc<Self>,
+        mut io: Stream,
+        shutdown: &ShutdownWatch,
+    ) -> Option<Stream> {
+        let digest = io.get_socket_digest();
+        let client_addr = digest
+            .as_ref()
+            .and_then(|d| d.peer_addr())
+            .and_then(|a| a.as_inet())
+            .copied();
+        let Some(server_port) = digest
+            .as_ref()
+            .and_then(|d| d.local_addr())
+            .and_then(|a| a.as_inet())
+            .map(|a| a.port())
+        else {
+            log::warn!("Stream connection without a local address, closing");
+            return None;
+        };
+        let client_ip = client_addr.map(|a| a.ip());
+
+        // Bytes read while looking for SNI are replayed to the upstream
+        let mut preread = Vec::new();
+        let sni = if stream_routes_need_sni(server_port) {
+            read_client_hello_sni(&mut io, &mut preread).await
+        } else {
+            None
+        };
+
+        let Some(route) = stream_route_match(server_port, client_ip, sni.as_deref()) else {
+            log::debug!("No stream route matched {client_addr:?} on port {server_port}");
+            return None;
+        };
+        let Some(upstream) = route.resolve_upstream() else {
+            log::error!("Stream route '{}' has no upstream", route.inner.id);
+            return None;
+        };
+
+        let mut upstream_io = match connect_upstream(&upstream, client_ip).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::error!("Stream route '{}' failed to connect: {}", route.inner.id, e);
+                return None;
+            }
+        };
+        if !preread.is_empty() {
+            if let Err(e) = upstream_io.write_all(&preread).await {
+                log::error!("Stream route '{}' failed to write: {}", route.inner.id, e);
+                return None;
+            }
+        }
+
+        let mut shutdown = shutdown.clone();
+        tokio::select! {
+            result = copy_bidirectional(&mut io, &mut upstream_io) => match result {
+                Ok((sent, received)) => log::debug!(
+                    "Stream route '{}' closed: {sent} bytes sent, {received} bytes received",
+                    route.inner.id
+                ),
+                Err(e) => log::debug!("Stream route '{}' closed: {e}", route.inner.id),
+            },
+            _ = shutdown.changed() => {}
+        }
+
+        None
+    }
+}
+
+/// Selects a backend address, hashing on the client IP for hash-based balancers.
+fn select_backend_addr(
+    upstream: &ProxyUpstream,
+    key: &str,
+    excluded: &[BackendAddr],
+) -> ProxyResult<BackendAddr> {
+    upstream
+        .select_backend_by_key(key.as_bytes(), excluded)
+        .map(|backend| backend.addr)
+        .ok_or_else(|| {
+            ProxyError::UpstreamSelection(format!(
+                "No healthy backend in upstream '{}'",
+                upstream.inner.id
+            ))
+        })
+}
+
+/// Connects to a backend of `upstream`, trying another backend up to `retries` times.
+///
+/// Failed connects are reported to the passive health check, and retries avoid the nodes
+/// that already failed, also for hash-based balancers.
+async fn connect_upstream(
+    upstream: &ProxyUpstream,
+    client_ip: Option<IpAddr>,
+) -> ProxyResult<Box<dyn UpstreamIo>> {
+    let key = client_ip.map(|ip| ip.to_string()).unwrap_or_default();
+    let connect_timeout = upstream
+        .inner
+        .timeout
+        .as_ref()
+        .map_or(DEFAULT_CONNECT_TIMEOUT, |t| Duration::from_secs(t.connect));
+    let tries = upstream.inner.retries.unwrap_or(0) as usize + 1;
+
+    let mut last_error = None;
+    let mut failed = Vec::new();
+    for _ in 0..tries {
+        let addr = select_backend_addr(upstream, &key, &failed)?;
+        let connect = async {
+            Ok::<Box<dyn UpstreamIo>, std::io::Error>(match &addr {
+                BackendAddr::Inet(inet) => Box::new(TcpStream::connect(inet).await?),
+                // Displays as the socket path
+                BackendAddr::Unix(_) => Box::new(UnixStream::connect(addr.to_string()).await?),
+            })
+        };
+        let (error, outcome) = match timeout(connect_timeout, connect).await {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => (ProxyError::Network(e), UpstreamOutcome::TcpFailure),
+            Err(_) => (
+                ProxyError::Network(std::io::ErrorKind::TimedOut.into()),
+                UpstreamOutcome::Timeout,
+            ),
+        };
+        log::warn!("Failed to connect to stream backend {addr}: {error}");
+        upstream.report_node_outcome(&addr, outcome);
+        failed.push(addr);
+        last_error = Some(error);
+    }
+
+    Err(last_error.unwrap_or_else(|| ProxyError::Internal("No connect attempt".to_string())))
+}
+
+/// Reads from `io` until the TLS ClientHello is complete and returns its SNI.
+///
+/// Everything read is kept in `buf`. Returns `None` for non-TLS traffic, a
+/// ClientHello without SNI, or when the client does not send one in time.
+async fn read_client_hello_sni(io: &mut Stream, buf: &mut Vec<u8>) -> Option<String> {
+    let deadline = tokio::time::Instant::now() + CLIENT_HELLO_TIMEOUT;
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let ClientHello::Complete(sni) = parse_client_hello_sni(buf) {
+            return sni;
+        }
+        if buf.len() >= MAX_CLIENT_HELLO_LEN {
+            return None;
+        }
+        match tokio::time::timeout_at(deadline, io.read(&mut chunk)).await {
+            Ok(Ok(n)) if n > 0 => buf.extend_from_slice(&chunk[..n]),
+            _ => return None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ClientHello {
+    /// More bytes are needed.
+    Incomplete,
+    /// The first record was read; carries the SNI if it was a ClientHello with one.
+    Complete(Option<String>),
+}
+
+/// Extracts the server name from a TLS ClientHello at the start of `buf`.
+fn parse_client_hello_sni(buf: &[u8]) -> ClientHello {
+    const HANDSHAKE: u8 = 0x16;
+    const CLIENT_HELLO: u8 = 0x01;
+    const SERVER_NAME_EXT: u16 = 0x0000;
+
+    match buf.first() {
+        None => return ClientHello::Incomplete,
+        Some(&HANDSHAKE) => {}
+        Some(_) => return ClientHello::Complete(None),
+    }
+    if buf.len() < 5 {
+        return ClientHello::Incomplete;
+    }
+    let record_len = u16::from_be_bytes([buf[3], buf[4]]) as usize;
+    let Some(record) = buf.get(5..5 + record_len) else {
+        return ClientHello::Incomplete;
+    };
+
+    let sni = (|| {
+        let mut r = Reader(record);
+        if r.u8()? != CLIENT_HELLO {
+            return None;
+        }
+        let hello_len = r.u24()?;
+        let mut hello = Reader(r.take(hello_len)?);
+        hello.take(2 + 32)?; // version, random
+        let session_id_len = hello.u8()? as usize;
+        hello.take(session_id_len)?;
+        let cipher_suites_len = hello.u16()? as usize;
+        hello.take(cipher_suites_len)?;
+        let compression_len = hello.u8()? as usize;
+        hello.take(compression_len)?;
+
+        let extensions_len = hello.u16()? as usize;
+        let mut extensions = Reader(hello.take(extensions_len)?);
+        while !extensions.0.is_empty() {
+            let ext_type = extensions.u16()?;
+            let ext_len = extensions.u16()? as usize;
+            let mut ext = Reader(extensions.take(ext_len)?);
+            if ext_type != SERVER_NAME_EXT {
+                continue;
+            }
+            let list_len = ext.u16()? as usize;
+            let mut list = Reader(ext.take(list_len)?);
+            while !list.0.is_empty() {
+                let name_type = list.u8()?;
+                let name_len = list.u16()? as usize;
+                let name = list.take(name_len)?;
+                if name_type == 0 {
+                    return std::str::from_utf8(name).ok().map(str::to_string);
+                }
+            }
+        }
+        None
+    })();
+
+    ClientHello::Complete(sni)
+}
+
+/// Minimal big-endian cursor over a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3)
+            .map(|b| (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize)
+    }
+}
+
+/// UDP proxy forwarding datagrams to the upstream of the matched stream route.
+///
+/// Each client address gets a session with its own upstream socket, so replies are
+/// routed back to the right client. Sessions close after `udp_idle_timeout`.
+pub struct StreamUdpService {
+    addrs: Vec<SocketAddr>,
+    idle_timeout: Duration,
+    max_sessions: usize,
+}
+
+impl StreamUdpService {
+    pub fn new(cfg: &StreamProxy) -> Self {
+        Self {
+            addrs: cfg.udp.clone(),
+            idle_timeout: Duration::from_secs(cfg.udp_idle_timeout),
+            max_sessions: cfg.udp_max_sessions,
+        }
+    }
+}
+
+#[async_trait]
+impl ServiceTrait for StreamUdpService {
+    async fn start_service(
+        &mut self,
+        #[cfg(unix)] _fds: Option<pingora_core::server::ListenFds>,
+        mut shutdown: ShutdownWatch,
+        _listeners_per_fd: usize,
+    ) {
+        for addr in self.addrs.iter() {
+            match UdpSocket::bind(addr).await {
+                Ok(socket) => {
+                    log::info!("Stream UDP listening on {addr}");
+                    tokio::spawn(serve_udp(
+                        Arc::new(socket),
+                        self.idle_timeout,
+                        self.max_sessions,
+                        shutdown.clone(),
+                    ));
+                }
+                Err(e) => log::error!("Failed to bind stream UDP listener {addr}: {e}"),
+            }
+        }
+
+        let _ = shutdown.changed().await;
+    }
+
+    fn name(&self) -> &str {
+        "Stream UDP"
+    }
+
+    fn threads(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// A client's UDP session with its upstream backend.
+struct UdpSession {
+    upstream: UdpSocket,
+    created: Instant,
+    /// Milliseconds since `created` of the last datagram in either direction.
+    last_active: AtomicU64,
+}
+
+impl UdpSession {
+    fn touch(&self) {
+        self.last_active
+            .store(self.created.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.created.elapsed().saturating_sub(Duration::from_millis(
+            self.last_active.load(Ordering::Relaxed),
+        ))
+    }
+}
+
+type UdpSessions = DashMap<SocketAddr, Arc<UdpSession>>;
+
+async fn serve_udp(
+    socket: Arc<UdpSocket>,
+    idle_timeout: Duration,
+    max_sessions: usize,
+    mut shutdown: ShutdownWatch,
+) {
+    let server_port = match socket.local_addr() {
+        Ok(addr) => addr.port(),
+        Err(e) => {
+            log::error!("Stream UDP socket without a local address: {e}");
+            return;
+        }
+    };
+    let sessions: Arc<UdpSessions> = Arc::new(DashMap::new());
+    let mut buf = vec![0u8; MAX_DATAGRAM_LEN];
+
+    loop {
+        let (len, client) = tokio::select! {
+            result = socket.recv_from(&mut buf) => match result {
+                Ok(received) => received,
+                Err(e) => {
+                    log::warn!("Stream UDP receive failed: {e}");
+                    tokio::time::sleep(UDP_RECV_ERROR_BACKOFF).await;
+                    continue;
+                }
+            },
+            _ = shutdown.changed() => return,
+        };
+
+        let session = match sessions.get(&client).map(|s| s.value().clone()) {
+            Some(session) => session,
+            // Every session holds a socket, so spoofed sources must not open them unbounded
+            None if sessions.len() >= max_sessions => {
+                log::debug!("Dropping datagram from {client}: {max_sessions} UDP sessions open");
+                continue;
+            }
+            None => match open_udp_session(server_port, client).await {
+                Ok(session) => {
+                    sessions.insert(client, session.clone());
+                    tokio::spawn(relay_udp_replies(
+                        socket.clone(),
+                        client,
+                        session.clone(),
+                        sessions.clone(),
+                        idle_timeout,
+                    ));
+                    session
+                }
+                Err(e) => {
+                    log::debug!("Dropping datagram from {client}: {e}");
+                    continue;
+                }
+            },
+        };
+
+        session.touch();
+        if let Err(e) = session.upstream.send(&buf[..len]).await {
+            log::debug!("Stream UDP send to upstream failed: {e}");
+        }
+    }
+}
+
+async fn open_udp_session(server_port: u16, client: SocketAddr) -> ProxyResult<Arc<UdpSession>> {
+    let route = stream_route_match(server_port, Some(client.ip()), None).ok_or_else(|| {
+        ProxyError::RouteMatching(format!("No stream route on port {server_port}"))
+    })?;
+    let upstream = route.resolve_upstream().ok_or_else(|| {
+        ProxyError::UpstreamSelection(format!("Stream route '{}' has no upstream", route.inner.id))
+    })?;
+    let backend = match select_backend_addr(&upstream, &client.ip().to_string(), &[])? {
+        BackendAddr::Inet(addr) => addr,
+        BackendAddr::Unix(_) => {
+            return Err(ProxyError::UpstreamSelection(format!(
+                "Upstream '{}' of UDP stream route '{}' has a Unix socket node",
+                upstream.inner.id, route.inner.id
+            )))
+        }
+    };
+
+    let local: SocketAddr = if backend.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(backend).await?;
+    log::debug!(
+        "Stream route '{}' opened UDP session {client} -> {backend}",
+        route.inner.id
+    );
+
+    Ok(Arc::new(UdpSession {
+        upstream: socket,
+        created: Instant::now(),
+        last_active: AtomicU64::new(0),
+    }))
+}
+
+async fn relay_udp_replies(
+    socket: Arc<UdpSocket>,
+    client: SocketAddr,
+    session: Arc<UdpSession>,
+    sessions: Arc<UdpSessions>,
+    idle_timeout: Duration,
+) {
+    let mut buf = vec![0u8; MAX_DATAGRAM_LEN];
+    loop {
+        match timeout(idle_timeout, session.upstream.recv(&mut buf)).await {
+            Ok(Ok(len)) => {
+                session.touch();
+                if let Err(e) = socket.send_to(&buf[..len], client).await {
+                    log::debug!("Stream UDP send to {client} failed: {e}");
+                }
+            }
+            Ok(Err(e)) => {
+                log::debug!("Stream UDP session {client} closed: {e}");
+                break;
+            }
+            Err(_) if session.idle_for() >= idle_timeout => break,
+            Err(_) => {}
+        }
+    }
+    sessions.remove(&client);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a ClientHello record carrying `sni`.
+    fn client_hello(sni: &str) -> Vec<u8> {
+        let name = sni.as_bytes();
+        let mut server_name = vec![0x00];
+        server_name.extend((name.len() as u16).to_be_bytes());
+        server_name.extend(name);
+        let mut ext = (server_name.len() as u16).to_be_bytes().to_vec();
+        ext.extend(server_name);
+        let mut extensions = vec![0x00, 0x00];
+        extensions.extend((ext.len() as u16).to_be_bytes());
+        extensions.extend(ext);
+
+        let mut hello = vec![0x03, 0x03];
+        hello.extend([0u8; 32]);
+        hello.push(0); // session id
+        hello.extend([0x00, 0x02, 0x13, 0x01]); // cipher suites
+        hello.extend([0x01, 0x00]); // compression
+        hello.extend((extensions.len() as u16).to_be_bytes());
+        hello.extend(extensions);
+
+        let mut handshake = vec![0x01];
+        handshake.extend(&(hello.len() as u32).to_be_bytes()[1..]);
+        handshake.extend(hello);
+
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend((handshake.len() as u16).to_be_bytes());
+        record.extend(handshake);
+        record
+    }
+
+    #[test]
+    fn test_parse_client_hello_sni() {
+        let record = client_hello("db.example.com");
+        assert_eq!(
+            parse_client_hello_sni(&record),
+            ClientHello::Complete(Some("db.example.com".to_string()))
+        );
+        assert_eq!(
+            parse_client_hello_sni(&record[..record.len() - 1]),
+            ClientHello::Incomplete
+        );
+        assert_eq!(parse_client_hello_sni(&[]), ClientHello::Incomplete);
+        assert_eq!(
+            parse_client_hello_sni(b"GET / HTTP/1.1\r\n"),
+            ClientHello::Complete(None)
+        );
+    }
+}
@@ -1,2 +1,3 @@
 pub mod request;
 pub mod response;
+pub mod vars;
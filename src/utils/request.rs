@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use http::HeaderName;
 use once_cell::sync::Lazy;
 use pingora_http::RequestHeader;
@@ -63,6 +65,73 @@ fn handle_vars(session: &mut Session, key: &str) -> String {
     }
 }
 
+/// Resolves an APISIX-style request variable.
+///
+/// Supports `arg_*` (query), `http_*` (header, `_` mapped to `-`), `cookie_*`, and the
+/// builtins `uri`, `request_uri`, `query_string`, `host`, `request_method`, `scheme`,
+/// `remote_addr`, `remote_port`, `server_addr` and `server_port`.
+/// Returns `None` when the variable is unknown or absent from the request.
+pub fn get_request_var<'a>(session: &'a Session, name: &str) -> Option<Cow<'a, str>> {
+    let req_header = session.req_header();
+
+    if let Some(arg) = name.strip_prefix("arg_") {
+        return get_query_value(req_header, arg).map(Cow::Borrowed);
+    }
+    if let Some(header) = name.strip_prefix("http_") {
+        return get_req_header_value(req_header, &header.replace('_', "-")).map(Cow::Borrowed);
+    }
+    if let Some(cookie) = name.strip_prefix("cookie_") {
+        return get_cookie_value(req_header, cookie).map(Cow::Borrowed);
+    }
+
+    match name {
+        "uri" => Some(Cow::Borrowed(req_header.uri.path())),
+        "request_uri" => Some(
+            req_header
+                .uri
+                .path_and_query()
+                .map_or(Cow::Borrowed(req_header.uri.path()), |pq| {
+                    Cow::Borrowed(pq.as_str())
+                }),
+        ),
+        "query_string" => req_header.uri.query().map(Cow::Borrowed),
+        "host" => get_request_host(req_header).map(Cow::Borrowed),
+        "request_method" => Some(Cow::Borrowed(req_header.method.as_str())),
+        "scheme" => Some(Cow::Borrowed(if is_tls_session(session) {
+            "https"
+        } else {
+            "http"
+        })),
+        "remote_addr" => session
+            .client_addr()
+            .and_then(|addr| addr.as_inet())
+            .map(|inet| Cow::Owned(inet.ip().to_string())),
+        "remote_port" => session
+            .client_addr()
+            .and_then(|addr| addr.as_inet())
+            .map(|inet| Cow::Owned(inet.port().to_string())),
+        "server_addr" => session
+            .server_addr()
+            .and_then(|addr| addr.as_inet())
+            .map(|inet| Cow::Owned(inet.ip().to_string())),
+        "server_port" => session
+            .server_addr()
+            .and_then(|addr| addr.as_inet())
+            .map(|inet| Cow::Owned(inet.port().to_string())),
+        _ => {
+            log::debug!("Unsupported request variable: {name}");
+            None
+        }
+    }
+}
+
+/// Returns true if the downstream connection is TLS-terminated by this proxy.
+pub fn is_tls_session(session: &Session) -> bool {
+    session
+        .digest()
+        .is_some_and(|digest| digest.ssl_digest.is_some())
+}
+
 /// Extracts the value of a specific query parameter from the request URI.
 ///
 /// Returns the first occurrence of the parameter's value.
@@ -0,0 +1,280 @@
+//! APISIX-style `vars` expressions.
+//!
+//! An expression list such as `[["http_x_env", "==", "beta"], ["arg_v", "in", ["2", "3"]]]`
+//! is compiled once at configuration time and evaluated per request. All expressions
+//! must match (logical AND). Each expression may be negated by inserting `"!"` before
+//! the operator, e.g. `["arg_debug", "!", "==", "1"]`.
+
+use std::{borrow::Cow, net::IpAddr};
+
+use ipnetwork::IpNetwork;
+use pingora_proxy::Session;
+use regex::{Regex, RegexBuilder};
+use serde_json::Value as JsonValue;
+
+use crate::core::{ProxyError, ProxyResult};
+
+use super::request::get_request_var;
+
+/// Comparison applied to the resolved variable value.
+#[derive(Debug)]
+enum VarOperator {
+    /// `==`: string equality.
+    Eq(String),
+    /// `~=`: string inequality.
+    NotEq(String),
+    /// `>`, `>=`, `<`, `<=`: numeric comparison.
+    Gt(f64),
+    Ge(f64),
+    Lt(f64),
+    Le(f64),
+    /// `~~` (case-sensitive) and `~*` (case-insensitive): regex match.
+    Regex(Regex),
+    /// `in`: value is one of the listed strings.
+    In(Vec<String>),
+    /// `has`: value contains the given substring.
+    Has(String),
+    /// `ipmatch`: value is an IP contained in one of the CIDRs.
+    IpMatch(Vec<IpNetwork>),
+}
+
+#[derive(Debug)]
+struct VarExpr {
+    name: String,
+    negate: bool,
+    op: VarOperator,
+}
+
+/// A compiled list of `vars` expressions.
+#[derive(Debug, Default)]
+pub struct VarsExpr {
+    exprs: Vec<VarExpr>,
+}
+
+impl VarsExpr {
+    /// Compiles raw `vars` configuration, rejecting unknown operators and malformed values.
+    pub fn compile(vars: &[Vec<JsonValue>]) -> ProxyResult<Self> {
+        let exprs = vars
+            .iter()
+            .map(|expr| VarExpr::compile(expr))
+            .collect::<ProxyResult<Vec<_>>>()?;
+        Ok(Self { exprs })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+
+    /// Evaluates the expressions against the request variables of `session`.
+    pub fn matches(&self, session: &Session) -> bool {
+        self.matches_with(|name| get_request_var(session, name))
+    }
+
+    /// Evaluates the expressions using `lookup` to resolve variable values.
+    fn matches_with<'a, F>(&self, lookup: F) -> bool
+    where
+        F: Fn(&str) -> Option<Cow<'a, str>>,
+    {
+        self.exprs.iter().all(|expr| {
+            let value = lookup(&expr.name);
+            expr.op.eval(value.as_deref()) != expr.negate
+        })
+    }
+}
+
+impl VarExpr {
+    fn compile(expr: &[JsonValue]) -> ProxyResult<Self> {
+        let invalid = |msg: &str| {
+            ProxyError::Configuration(format!("Invalid vars expression {expr:?}: {msg}"))
+        };
+
+        let (name, negate, op, value) = match expr {
+            [name, op, value] => (name, false, op, value),
+            [name, not, op, value] if not.as_str() == Some("!") => (name, true, op, value),
+            _ => return Err(invalid("expected [var, operator, value]")),
+        };
+
+        let name = name
+            .as_str()
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| invalid("variable name must be a non-empty string"))?;
+        let op = op
+            .as_str()
+            .ok_or_else(|| invalid("operator must be a string"))?;
+
+        let op = match op {
+            "==" => VarOperator::Eq(json_to_string(value).ok_or_else(|| invalid("bad value"))?),
+            "~=" => VarOperator::NotEq(json_to_string(value).ok_or_else(|| invalid("bad value"))?),
+            ">" | ">=" | "<" | "<=" => {
+                let num = json_to_number(value)
+                    .ok_or_else(|| invalid("numeric comparison requires a number"))?;
+                match op {
+                    ">" => VarOperator::Gt(num),
+                    ">=" => VarOperator::Ge(num),
+                    "<" => VarOperator::Lt(num),
+                    _ => VarOperator::Le(num),
+                }
+            }
+            "~~" | "~*" => {
+                let pattern = value
+                    .as_str()
+                    .ok_or_else(|| invalid("regex operator requires a string pattern"))?;
+                let re = RegexBuilder::new(pattern)
+                    .case_insensitive(op == "~*")
+                    .build()
+                    .map_err(|e| invalid(&format!("invalid regex: {e}")))?;
+                VarOperator::Regex(re)
+            }
+            "in" => {
+                let list = value
+                    .as_array()
+                    .ok_or_else(|| invalid("'in' operator requires an array"))?
+                    .iter()
+                    .map(json_to_string)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid("'in' values must be scalars"))?;
+                VarOperator::In(list)
+            }
+            "has" => VarOperator::Has(json_to_string(value).ok_or_else(|| invalid("bad value"))?),
+            "ipmatch" => {
+                let cidrs: Vec<&JsonValue> = match value {
+                    JsonValue::Array(items) => items.iter().collect(),
+                    other => vec![other],
+                };
+                let networks = cidrs
+                    .into_iter()
+                    .map(|c| {
+                        c.as_str()
+                            .and_then(|s| s.parse::<IpNetwork>().ok())
+                            .ok_or_else(|| invalid(&format!("invalid CIDR {c}")))
+                    })
+                    .collect::<ProxyResult<Vec<_>>>()?;
+                VarOperator::IpMatch(networks)
+            }
+            other => return Err(invalid(&format!("unsupported operator '{other}'"))),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            negate,
+            op,
+        })
+    }
+}
+
+impl VarOperator {
+    /// Missing variables never satisfy an operator (a negated expression then matches).
+    fn eval(&self, value: Option<&str>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+
+        match self {
+            VarOperator::Eq(expected) => value == expected,
+            VarOperator::NotEq(expected) => value != expected,
+            VarOperator::Gt(n) => value.parse::<f64>().is_ok_and(|v| v > *n),
+            VarOperator::Ge(n) => value.parse::<f64>().is_ok_and(|v| v >= *n),
+            VarOperator::Lt(n) => value.parse::<f64>().is_ok_and(|v| v < *n),
+            VarOperator::Le(n) => value.parse::<f64>().is_ok_and(|v| v <= *n),
+            VarOperator::Regex(re) => re.is_match(value),
+            VarOperator::In(list) => list.iter().any(|item| item == value),
+            VarOperator::Has(needle) => value.contains(needle.as_str()),
+            VarOperator::IpMatch(networks) => value
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| networks.iter().any(|n| n.contains(ip))),
+        }
+    }
+}
+
+fn json_to_string(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(s) => Some(s.clone()),
+        JsonValue::Number(n) => Some(n.to_string()),
+        JsonValue::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn json_to_number(value: &JsonValue) -> Option<f64> {
+    match value {
+        JsonValue::Number(n) => n.as_f64(),
+        JsonValue::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn compile(value: JsonValue) -> ProxyResult<VarsExpr> {
+        let vars: Vec<Vec<JsonValue>> = serde_json::from_value(value).unwrap();
+        VarsExpr::compile(&vars)
+    }
+
+    fn eval(expr: &VarsExpr, vars: &[(&'static str, &'static str)]) -> bool {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        expr.matches_with(|name| vars.get(name).map(|v| Cow::Borrowed(*v)))
+    }
+
+    #[test]
+    fn test_vars_operators() {
+        let expr = compile(json!([
+            ["http_x_env", "==", "beta"],
+            ["arg_v", ">=", 2],
+            ["uri", "~~", "^/api/"],
+            ["cookie_tier", "in", ["gold", "silver"]],
+            ["remote_addr", "ipmatch", ["10.0.0.0/8"]]
+        ]))
+        .unwrap();
+
+        let matching = [
+            ("http_x_env", "beta"),
+            ("arg_v", "3"),
+            ("uri", "/api/users"),
+            ("cookie_tier", "gold"),
+            ("remote_addr", "10.1.2.3"),
+        ];
+        assert!(eval(&expr, &matching));
+
+        let mut wrong_ip = matching;
+        wrong_ip[4] = ("remote_addr", "192.168.1.1");
+        assert!(!eval(&expr, &wrong_ip));
+
+        let mut old_version = matching;
+        old_version[1] = ("arg_v", "1");
+        assert!(!eval(&expr, &old_version));
+
+        // Missing variable never matches
+        assert!(!eval(&expr, &matching[1..]));
+    }
+
+    #[test]
+    fn test_vars_negation_and_case_insensitive_regex() {
+        let expr = compile(json!([
+            ["arg_debug", "!", "==", "1"],
+            ["http_user_agent", "~*", "curl"]
+        ]))
+        .unwrap();
+
+        assert!(eval(&expr, &[("http_user_agent", "CURL/8.0")]));
+        assert!(!eval(
+            &expr,
+            &[("arg_debug", "1"), ("http_user_agent", "curl/8.0")]
+        ));
+    }
+
+    #[test]
+    fn test_vars_compile_errors() {
+        assert!(compile(json!([["arg_v", "=~", "x"]])).is_err());
+        assert!(compile(json!([["arg_v", "=="]])).is_err());
+        assert!(compile(json!([["arg_v", "in", "x"]])).is_err());
+        assert!(compile(json!([["uri", "~~", "("]])).is_err());
+        assert!(compile(json!([["remote_addr", "ipmatch", "not-a-cidr"]])).is_err());
+        assert!(compile(json!([])).unwrap().is_empty());
+    }
+}